@@ -0,0 +1,82 @@
+//! Emulation speed of each backend for batch runs, with `cargo bench`:
+//! frames per second on a few games, and instructions per second on a loop
+//! that never draws or waits for a key.
+
+use std::time::{Duration, Instant};
+
+use chip8::{Backend, Chip8, Chip8Builder, Platform};
+
+const FRAMES: u64 = 3000;
+
+// arithmetic and a jump back, forever
+const ALU_LOOP: [u8; 16] = [
+    0x70, 0x01, // 200: V0 += 1
+    0x81, 0x04, // 202: V1 += V0
+    0x82, 0x13, // 204: V2 ^= V1
+    0x83, 0x25, // 206: V3 -= V2
+    0x84, 0x36, // 208: V4 >>= 1
+    0x62, 0x07, // 20A: V2 = 7
+    0xA3, 0x00, // 20C: I = 300
+    0x12, 0x00, // 20E: jump 200
+];
+
+const GAMES: [&str; 3] = [
+    "roms/games/Brix [Andreas Gustafsson, 1990].ch8",
+    "roms/games/Space Invaders [David Winter].ch8",
+    "roms/games/Tetris [Fran Dachille, 1991].ch8",
+];
+
+/// Runs `chip8` for `FRAMES` frames, pressing a key now and then so games
+/// get past their title screens.
+fn run(mut chip8: Chip8) -> Duration {
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        let mut keys = [false; 16];
+        if frame % 40 < 10 {
+            keys[(frame / 40 * 7 % 16) as usize] = true;
+        }
+        chip8.set_keys(keys);
+        chip8.step_frame().unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for backend in [Backend::Interpreter, Backend::Blocks] {
+        // no display wait, every frame runs all of its instructions
+        let tick_rate = 10_000;
+        let chip8 = Chip8Builder::new()
+            .rom_bytes(ALU_LOOP)
+            .platform(Platform::Superchip)
+            .tick_rate(tick_rate)
+            .seed(1)
+            .backend(backend)
+            .build()
+            .unwrap();
+        let elapsed = run(chip8);
+        let instructions = (FRAMES * tick_rate as u64) as f64;
+        println!(
+            "{:?}, alu loop: {:.0} M instructions/s",
+            backend,
+            instructions / elapsed.as_secs_f64() / 1e6
+        );
+
+        for game in GAMES {
+            let chip8 = Chip8Builder::new()
+                .rom_path(game)
+                .unwrap()
+                .tick_rate(1000)
+                .seed(1)
+                .backend(backend)
+                .build()
+                .unwrap();
+            let elapsed = run(chip8);
+            println!(
+                "{:?}, {}: {:.0} frames/s",
+                backend,
+                game,
+                FRAMES as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
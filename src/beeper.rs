@@ -0,0 +1,165 @@
+//! Turns the buzzer state of each frame into samples, for the speakers and
+//! for recordings alike.
+
+use std::f32::consts::TAU;
+
+use serde::Deserialize;
+
+use crate::chip8::buzzer::Buzzer;
+
+const FRAME_RATE: u64 = 60;
+const DEFAULT_FREQUENCY: f32 = 440.0;
+const DEFAULT_VOLUME: f32 = 0.25;
+// the sound fades in and out this fast, cutting it off at once clicks
+const ENVELOPE_SECONDS: f32 = 0.005;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    #[default]
+    Square,
+    /// A square wave with a 25% duty cycle, thinner than the square one.
+    Pulse,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    /// Level at `phase` into a period, from 0 to 1.
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Pulse if phase < 0.25 => 1.0,
+            Waveform::Square | Waveform::Pulse => -1.0,
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (phase * TAU).sin(),
+        }
+    }
+}
+
+/// Sound of the buzzer, set individually.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeeperSettings {
+    /// Pitch, in Hz.
+    pub frequency: Option<f32>,
+    pub waveform: Option<Waveform>,
+    /// From 0 for silence to 1 for full scale.
+    pub volume: Option<f32>,
+}
+
+impl BeeperSettings {
+    /// Layers `other` on top of `self`, values set in `other` win.
+    pub(crate) fn merge(&self, other: &BeeperSettings) -> BeeperSettings {
+        BeeperSettings {
+            frequency: other.frequency.or(self.frequency),
+            waveform: other.waveform.or(self.waveform),
+            volume: other.volume.or(self.volume),
+        }
+    }
+}
+
+pub(crate) struct Beeper {
+    sample_rate: u32,
+    frequency: f32,
+    waveform: Waveform,
+    volume: f32,
+    frames: u64,
+    // frames of sound per emulated frame, more when slowed down
+    stretch: u64,
+    phase: f32,
+    // envelope level, from 0 to 1
+    gain: f32,
+    samples: Vec<f32>,
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32, settings: &BeeperSettings) -> Beeper {
+        let mut beeper = Beeper {
+            sample_rate,
+            frequency: DEFAULT_FREQUENCY,
+            waveform: Waveform::default(),
+            volume: DEFAULT_VOLUME,
+            frames: 0,
+            stretch: 1,
+            phase: 0.0,
+            gain: 0.0,
+            samples: Vec::new(),
+        };
+        beeper.configure(settings);
+        beeper
+    }
+
+    pub fn configure(&mut self, settings: &BeeperSettings) {
+        // above half the sample rate, a tone can't be told apart from a lower one
+        let max_frequency = self.sample_rate as f32 / 2.0;
+        self.frequency = settings
+            .frequency
+            .unwrap_or(DEFAULT_FREQUENCY)
+            .clamp(1.0, max_frequency);
+        self.waveform = settings.waveform.unwrap_or_default();
+        self.volume = settings.volume.unwrap_or(DEFAULT_VOLUME).clamp(0.0, 1.0);
+    }
+
+    /// Makes every following frame `stretch` frames long, to keep up with
+    /// an emulation slowed down as many times. The pitch stays the same.
+    pub fn set_stretch(&mut self, stretch: u64) {
+        self.stretch = stretch.max(1);
+    }
+
+    /// Renders one frame of the buzzer. Frames don't all have the same
+    /// number of samples, so the sound never drifts away from emulated time.
+    pub fn frame(&mut self, buzzer: &Buzzer) -> &[f32] {
+        let start = self.frames * self.sample_rate as u64 / FRAME_RATE;
+        self.frames += self.stretch;
+        let end = self.frames * self.sample_rate as u64 / FRAME_RATE;
+        let count = (end - start) as usize;
+
+        let envelope_step = 1.0 / (ENVELOPE_SECONDS * self.sample_rate as f32);
+        self.samples.clear();
+        for index in 0..count {
+            self.gain = match buzzer.is_on(index as f32 / count as f32) {
+                true => (self.gain + envelope_step).min(1.0),
+                false => (self.gain - envelope_step).max(0.0),
+            };
+            let sample = self.waveform.sample(self.phase) * self.volume * self.gain;
+            self.phase = (self.phase + self.frequency / self.sample_rate as f32) % 1.0;
+            self.samples.push(sample);
+        }
+        &self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Times the samples of `samples` cross from negative to positive.
+    fn cycles(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn stretches_frames_at_the_same_pitch() {
+        let mut buzzer = Buzzer::default();
+        buzzer.begin(true);
+        let settings = BeeperSettings {
+            frequency: Some(600.0),
+            ..Default::default()
+        };
+        let mut beeper = Beeper::new(48000, &settings);
+        let normal = beeper.frame(&buzzer).to_vec();
+        assert_eq!(normal.len(), 800);
+        assert_eq!(cycles(&normal), 9);
+
+        beeper.set_stretch(4);
+        let stretched = beeper.frame(&buzzer).to_vec();
+        assert_eq!(stretched.len(), 4 * 800);
+        assert_eq!(cycles(&stretched), 40);
+        // the buzzer stays on all along, with no gap of silence
+        assert!(stretched.iter().all(|sample| *sample != 0.0));
+    }
+}
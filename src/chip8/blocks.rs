@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::opcode::Opcode;
+
+use super::{vm::MEMORY_SIZE, VM};
+
+// caps how far back a memory write has to look for blocks it overwrites
+const MAX_BLOCK_LENGTH: usize = 32;
+// programs rewriting their code all the time keep adding blocks, past this
+// many instructions they start over
+const MAX_DECODED: usize = 1 << 16;
+
+/// Which way a [`VM`](super::VM) runs instructions. Both behave exactly the
+/// same, down to the timing within frames.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Decodes and runs one instruction at a time.
+    #[default]
+    Interpreter,
+    /// Translates straight-line runs of instructions, up to the next jump,
+    /// skip, draw, key wait, sound timer or memory write, into handlers
+    /// bound to their operands and to the quirks, then runs a whole block
+    /// at a time. Faster for batch runs, see `cargo bench`.
+    Blocks,
+}
+
+/// An instruction translated for the blocks backend: the handler running it
+/// with its operands already picked out, the ones of JumpOffset and of the
+/// shifts depending on the quirks. The program counter is already past the
+/// instruction when `run` is called.
+#[derive(Clone, Copy)]
+pub(super) struct Op {
+    pub run: fn(&mut VM, Op) -> Result<()>,
+    pub x: usize,
+    pub y: usize,
+    /// NN or NNN.
+    pub n: u16,
+    /// For observers, and for handlers running it as the interpreter does.
+    pub opcode: Opcode,
+}
+
+/// Straight-line runs of decoded instructions, by start address. Every
+/// memory write has to invalidate the blocks it overwrites, for programs
+/// modifying their own code. Nothing is allocated until the first block.
+#[derive(Default)]
+pub(super) struct Blocks {
+    // the instructions of every block, one after the other
+    ops: Vec<Op>,
+    // where the block starting at each address is in `ops`
+    blocks: Vec<Option<(usize, usize)>>,
+    // bytes some block was decoded from, other writes need no lookup
+    code: Vec<bool>,
+}
+
+impl Blocks {
+    pub fn get(&self, address: u16) -> Option<Range<usize>> {
+        let (start, end) = self.blocks.get(address as usize).copied().flatten()?;
+        Some(start..end)
+    }
+
+    #[inline]
+    pub fn op(&self, index: usize) -> Op {
+        self.ops[index]
+    }
+
+    /// Translates the block starting at `address` with `translate`, which
+    /// gives the instruction at an address. Fails only when the first
+    /// instruction does, the others fail when reached, as they would when
+    /// interpreted.
+    pub fn translate<F>(&mut self, address: u16, translate: F) -> Result<Range<usize>>
+    where
+        F: Fn(u16) -> Result<Op>,
+    {
+        let first = translate(address)?;
+        if self.ops.len() > MAX_DECODED {
+            self.clear();
+        }
+        if self.blocks.is_empty() {
+            self.blocks.resize(MEMORY_SIZE, None);
+            self.code.resize(MEMORY_SIZE, false);
+        }
+
+        let start = self.ops.len();
+        self.ops.push(first);
+        let mut next = address;
+        while !ends_block(self.ops[self.ops.len() - 1].opcode)
+            && self.ops.len() - start < MAX_BLOCK_LENGTH
+        {
+            next += 2;
+            match translate(next) {
+                Ok(op) => self.ops.push(op),
+                Err(_) => break,
+            }
+        }
+
+        let end = self.ops.len();
+        let address = address as usize;
+        self.code[address..address + (end - start) * 2].fill(true);
+        self.blocks[address] = Some((start, end));
+        Ok(start..end)
+    }
+
+    /// Forgets the blocks using the byte at `address`.
+    pub fn invalidate(&mut self, address: usize) {
+        if self.code.get(address) != Some(&true) {
+            return;
+        }
+        let first = address.saturating_sub(MAX_BLOCK_LENGTH * 2 - 1);
+        for start in first..=address {
+            let overlaps = self.blocks[start].is_some_and(|(block_start, block_end)| {
+                start + (block_end - block_start) * 2 > address
+            });
+            if overlaps {
+                self.blocks[start] = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Blocks::default();
+    }
+}
+
+/// Whether the instructions after `opcode` may not run next, or were
+/// possibly overwritten. Drawing, waiting for a key and setting the sound
+/// timer end blocks too, so only the last instruction of a block can stop
+/// the frame or switch the buzzer.
+fn ends_block(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Return
+            | Opcode::Jump { .. }
+            | Opcode::CallSub { .. }
+            | Opcode::SkipIfEqualConst { .. }
+            | Opcode::SkipIfNotEqualConst { .. }
+            | Opcode::SkipIfEqual { .. }
+            | Opcode::SkipIfNotEqual { .. }
+            | Opcode::JumpOffset { .. }
+            | Opcode::SkipIfPress { .. }
+            | Opcode::SkipIfNotPress { .. }
+            | Opcode::Draw { .. }
+            | Opcode::AssignKey { .. }
+            | Opcode::SoundTimerAssign { .. }
+            | Opcode::BinaryCodedDecimal { .. }
+            | Opcode::RegDump { .. }
+    )
+}
@@ -0,0 +1,163 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    chip8::quirks::{Platform, Quirks},
+    config::Settings,
+};
+
+use super::{
+    vm::{FONT, MEMORY_SIZE, VM},
+    Backend,
+};
+
+/// Configures and creates a [`VM`], for applications embedding the
+/// interpreter. Nothing is opened until the VM is run on a frontend, so it
+/// can also be stepped headless with [`VM::step_frame`].
+///
+/// ```no_run
+/// use chip8::{Chip8Builder, Platform};
+///
+/// let mut chip8 = Chip8Builder::new()
+///     .rom_path("roms/games/Pong (1 player).ch8")?
+///     .platform(Platform::OriginalChip8)
+///     .seed(42)
+///     .build()?;
+/// for _ in 0..60 {
+///     chip8.step_frame()?;
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Chip8Builder {
+    pub(super) rom: Option<PathBuf>,
+    pub(super) content: Vec<u8>,
+    pub(super) settings: Settings,
+    pub(super) platform: Option<Platform>,
+    pub(super) quirks: Option<Quirks>,
+    pub(super) tick_rate: Option<usize>,
+    pub(super) load_address: u16,
+    pub(super) font_address: u16,
+    pub(super) font: [u8; 80],
+    pub(super) seed: Option<u64>,
+    pub(super) backend: Backend,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Chip8Builder {
+        Chip8Builder {
+            rom: None,
+            content: Vec::new(),
+            settings: Settings::default(),
+            platform: None,
+            quirks: None,
+            tick_rate: None,
+            load_address: 0x200,
+            font_address: 0,
+            font: FONT,
+            seed: None,
+            backend: Backend::default(),
+        }
+    }
+}
+
+impl Chip8Builder {
+    pub fn new() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    /// Loads the ROM from a file, which F3 reads again.
+    pub fn rom_path<P: AsRef<Path>>(mut self, path: P) -> Result<Chip8Builder> {
+        let path = path.as_ref();
+        self.content =
+            std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        self.rom = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    pub fn rom_bytes(mut self, content: impl Into<Vec<u8>>) -> Chip8Builder {
+        self.content = content.into();
+        self.rom = None;
+        self
+    }
+
+    pub fn rom_reader(mut self, mut reader: impl Read) -> Result<Chip8Builder> {
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .context("Cannot read ROM")?;
+        self.content = content;
+        self.rom = None;
+        Ok(self)
+    }
+
+    /// Settings as resolved from a [`Config`](crate::Config), for the keys,
+    /// colors and sound. The other options of the builder take precedence.
+    pub fn settings(mut self, settings: Settings) -> Chip8Builder {
+        self.settings = settings;
+        self
+    }
+
+    /// Where the ROM is loaded and run from, 0x200 by default, or 0x600 for
+    /// the ETI-660.
+    pub fn load_address(mut self, address: u16) -> Chip8Builder {
+        self.load_address = address;
+        self
+    }
+
+    /// Where the font is stored, which FX29 points I at. 0 by default, it
+    /// can't overlap the ROM.
+    pub fn font_address(mut self, address: u16) -> Chip8Builder {
+        self.font_address = address;
+        self
+    }
+
+    /// The 4x5 digits 0 to F, 5 bytes each.
+    pub fn font(mut self, font: [u8; 80]) -> Chip8Builder {
+        self.font = font;
+        self
+    }
+
+    pub fn platform(mut self, platform: Platform) -> Chip8Builder {
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Replaces the quirks of the platform.
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Instructions run per frame.
+    pub fn tick_rate(mut self, tick_rate: usize) -> Chip8Builder {
+        self.tick_rate = Some(tick_rate);
+        self
+    }
+
+    /// Seeds the random numbers of CXNN, so that a run can be repeated.
+    /// Without a seed they differ on every run.
+    pub fn seed(mut self, seed: u64) -> Chip8Builder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Chip8Builder {
+        self.backend = backend;
+        self
+    }
+
+    pub fn build(self) -> Result<VM> {
+        if self.load_address as usize >= MEMORY_SIZE {
+            bail!("Invalid load address {:#X}", self.load_address);
+        }
+        if self.font_address as usize + self.font.len() > MEMORY_SIZE {
+            bail!("Invalid font address {:#X}", self.font_address);
+        }
+
+        VM::new(self)
+    }
+}
@@ -0,0 +1,55 @@
+/// When the buzzer sounded during the last frame, it sounds while the sound
+/// timer is above 0.
+#[derive(Debug, Default)]
+pub struct Buzzer {
+    on_at_start: bool,
+    on: bool,
+    // where the buzzer turned on or off, as a fraction of the frame
+    toggles: Vec<f32>,
+}
+
+impl Buzzer {
+    /// Starts a new frame.
+    pub(crate) fn begin(&mut self, on: bool) {
+        self.on_at_start = on;
+        self.on = on;
+        self.toggles.clear();
+    }
+
+    /// Updates the state `position` into the frame, from 0 to 1.
+    pub(crate) fn set(&mut self, on: bool, position: f32) {
+        if on != self.on {
+            self.on = on;
+            self.toggles.push(position);
+        }
+    }
+
+    /// Whether the buzzer turned on during the frame, or at its start when
+    /// it was off at the end of the previous one, `was_on`.
+    pub(crate) fn started(&self, was_on: bool) -> bool {
+        // toggles alternate, starting with turning off when on at the start
+        let turned_on = self.toggles.len() > self.on_at_start as usize;
+        (self.on_at_start && !was_on) || turned_on
+    }
+
+    /// Whether the buzzer is on at the end of the frame.
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    /// Where the buzzer turned on or off during the frame, as fractions of
+    /// the frame from 0 to 1.
+    pub fn toggles(&self) -> &[f32] {
+        &self.toggles
+    }
+
+    /// Whether the buzzer is on `position` into the frame, from 0 to 1.
+    pub fn is_on(&self, position: f32) -> bool {
+        let toggles = self
+            .toggles
+            .iter()
+            .filter(|toggle| **toggle <= position)
+            .count();
+        self.on_at_start ^ (toggles % 2 == 1)
+    }
+}
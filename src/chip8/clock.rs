@@ -0,0 +1,243 @@
+use std::time::{Duration, Instant};
+
+const FRAME_RATE: u32 = 60;
+const FAST_FORWARD_RENDER_INTERVAL: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Speed {
+    Quarter,
+    Half,
+    Normal,
+    Double,
+    Uncapped,
+}
+
+impl Speed {
+    fn frame_duration(&self) -> Option<Duration> {
+        let normal = Duration::from_secs(1) / FRAME_RATE;
+        match self {
+            Speed::Quarter => Some(normal * 4),
+            Speed::Half => Some(normal * 2),
+            Speed::Normal => Some(normal),
+            Speed::Double => Some(normal / 2),
+            Speed::Uncapped => None,
+        }
+    }
+
+    /// How many times longer than normal a frame lasts, 1 when faster.
+    pub fn slowdown(&self) -> u64 {
+        match self {
+            Speed::Quarter => 4,
+            Speed::Half => 2,
+            Speed::Normal | Speed::Double | Speed::Uncapped => 1,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Speed::Quarter => "25%",
+            Speed::Half => "50%",
+            Speed::Normal => "100%",
+            Speed::Double => "200%",
+            Speed::Uncapped => "uncapped",
+        }
+    }
+}
+
+/// Paces emulated frames and keeps track of pause and frame advance requests.
+pub(crate) struct Clock {
+    speed: Speed,
+    paused: bool,
+    advance: bool,
+    fast_forward: bool,
+    frames: u64,
+    next_frame: Instant,
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock {
+            speed: Speed::Normal,
+            paused: false,
+            advance: false,
+            fast_forward: false,
+            frames: 0,
+            next_frame: Instant::now(),
+        }
+    }
+}
+
+impl Clock {
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses the clock and lets exactly one frame through.
+    pub fn advance(&mut self) {
+        self.paused = true;
+        self.advance = true;
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
+
+    pub fn fast_forwarding(&self) -> bool {
+        self.fast_forward && !self.paused
+    }
+
+    /// Number of frames emulated so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Whether a frame should be emulated in this iteration.
+    pub fn frame_due(&mut self) -> bool {
+        let due = if self.paused {
+            std::mem::take(&mut self.advance)
+        } else {
+            true
+        };
+        if due {
+            self.frames += 1;
+        }
+        due
+    }
+
+    /// Whether the current frame should be rendered, only every few frames
+    /// are shown while fast-forwarding.
+    pub fn render_due(&self) -> bool {
+        !self.fast_forwarding() || self.frames.is_multiple_of(FAST_FORWARD_RENDER_INTERVAL)
+    }
+
+    /// Sleeps until the next frame is due.
+    pub fn wait(&mut self) {
+        let frame_duration = if self.paused {
+            // keep polling input at the normal rate while paused
+            Speed::Normal.frame_duration()
+        } else if self.fast_forward {
+            Speed::Uncapped.frame_duration()
+        } else {
+            self.speed.frame_duration()
+        };
+
+        let Some(frame_duration) = frame_duration else {
+            self.next_frame = Instant::now();
+            return;
+        };
+
+        self.next_frame += frame_duration;
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        } else {
+            // running behind, don't try to catch up
+            self.next_frame = now;
+        }
+    }
+
+    pub fn status(&self) -> Option<String> {
+        match (self.paused, self.speed) {
+            (true, _) => Some("paused".to_string()),
+            (false, _) if self.fast_forward => Some("fast-forward".to_string()),
+            (false, Speed::Normal) => None,
+            (false, speed) => Some(speed.label().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses() {
+        let mut clock = Clock::default();
+        assert!(clock.frame_due());
+        clock.toggle_pause();
+        assert!(!clock.frame_due());
+        assert!(!clock.frame_due());
+        assert_eq!(clock.status().as_deref(), Some("paused"));
+        clock.toggle_pause();
+        assert!(clock.frame_due());
+        assert_eq!(clock.frames(), 2);
+        assert_eq!(clock.status(), None);
+    }
+
+    #[test]
+    fn advances_a_single_frame() {
+        let mut clock = Clock::default();
+        clock.advance();
+        assert!(clock.frame_due());
+        assert!(!clock.frame_due());
+        assert_eq!(clock.frames(), 1);
+        assert_eq!(clock.status().as_deref(), Some("paused"));
+
+        // while paused too
+        clock.advance();
+        assert!(clock.frame_due());
+        assert!(!clock.frame_due());
+        assert_eq!(clock.frames(), 2);
+    }
+
+    #[test]
+    fn paces_frames_at_each_speed() {
+        let normal = Duration::from_secs(1) / FRAME_RATE;
+        let cases = [
+            (Speed::Quarter, Some(normal * 4), Some("25%")),
+            (Speed::Half, Some(normal * 2), Some("50%")),
+            (Speed::Normal, Some(normal), None),
+            (Speed::Double, Some(normal / 2), Some("200%")),
+            (Speed::Uncapped, None, Some("uncapped")),
+        ];
+        for (speed, duration, status) in cases {
+            let mut clock = Clock::default();
+            clock.set_speed(speed);
+            assert_eq!(speed.frame_duration(), duration, "{:?}", speed);
+            assert_eq!(clock.status().as_deref(), status, "{:?}", speed);
+        }
+
+        let mut clock = Clock::default();
+        clock.set_speed(Speed::Double);
+        let start = Instant::now();
+        for _ in 0..4 {
+            clock.wait();
+        }
+        assert!(start.elapsed() >= normal * 2);
+
+        clock.set_speed(Speed::Uncapped);
+        let start = Instant::now();
+        for _ in 0..100 {
+            clock.wait();
+        }
+        assert!(start.elapsed() < normal * 100);
+    }
+
+    #[test]
+    fn renders_every_few_frames_while_fast_forwarding() {
+        let mut clock = Clock::default();
+        clock.set_fast_forward(true);
+        assert_eq!(clock.status().as_deref(), Some("fast-forward"));
+        let rendered = (0..30)
+            .filter(|_| clock.frame_due() && clock.render_due())
+            .count();
+        assert_eq!(rendered, 3);
+
+        // a paused frame is always shown, it is not fast-forwarded
+        clock.advance();
+        assert!(clock.frame_due());
+        assert!(clock.render_due());
+
+        clock.toggle_pause();
+        clock.set_fast_forward(false);
+        assert!((0..30).all(|_| clock.frame_due() && clock.render_due()));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::opcode::Opcode;
+
+use super::vm::MEMORY_SIZE;
+
+/// Instructions already decoded, by address, so running them again skips
+/// decoding. Every memory write has to invalidate what it overwrites.
+#[derive(Default)]
+pub(super) struct Decoded {
+    // empty until the first insert, only the interpreter backend uses it
+    opcodes: Vec<Option<Opcode>>,
+}
+
+impl Decoded {
+    pub fn get(&self, address: u16) -> Option<Opcode> {
+        self.opcodes.get(address as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, address: u16, opcode: Opcode) {
+        if self.opcodes.is_empty() {
+            self.opcodes.resize(MEMORY_SIZE, None);
+        }
+        self.opcodes[address as usize] = Some(opcode);
+    }
+
+    /// Forgets the instructions using the byte at `address`, the one
+    /// starting there and the one starting just before.
+    pub fn invalidate(&mut self, address: usize) {
+        if self.opcodes.is_empty() {
+            return;
+        }
+        self.opcodes[address] = None;
+        if let Some(previous) = address.checked_sub(1) {
+            self.opcodes[previous] = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.opcodes.fill(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_on_first_insert() {
+        let mut decoded = Decoded::default();
+        decoded.invalidate(0x200);
+        decoded.clear();
+        assert!(decoded.opcodes.is_empty());
+        assert_eq!(decoded.get(0x200), None);
+
+        decoded.insert(0x200, Opcode::ClearScreen);
+        assert_eq!(decoded.opcodes.len(), MEMORY_SIZE);
+        assert_eq!(decoded.get(0x200), Some(Opcode::ClearScreen));
+        // the second byte of the instruction is overwritten
+        decoded.invalidate(0x201);
+        assert_eq!(decoded.get(0x200), None);
+    }
+}
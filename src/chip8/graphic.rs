@@ -1,8 +1,10 @@
-use crate::ui::display::Display;
+use std::path::Path;
+
+use crate::{filter::Filter, palette::Palette, png::Image, ui::Frontend};
 use anyhow::Result;
 
-pub(crate) const HEIGHT: usize = 32;
-pub(crate) const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+pub const WIDTH: usize = 64;
 
 pub(crate) struct Graphic {
     inner: [[u8; WIDTH]; HEIGHT],
@@ -21,17 +23,42 @@ impl Default for Graphic {
 impl Graphic {
     pub fn clear(&mut self) {
         *self = Graphic::default();
+        self.rerender = true;
+    }
+
+    /// Pixels of the screen, 1 when lit.
+    pub fn pixels(&self) -> &[[u8; WIDTH]; HEIGHT] {
+        &self.inner
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        self.inner[y % HEIGHT][x % WIDTH] = lit as u8;
+        self.rerender = true;
+    }
+
+    /// Makes the next render redraw the whole screen.
+    pub fn invalidate(&mut self) {
+        self.rerender = true;
     }
 
-    pub fn draw(&mut self, vx: usize, vy: usize, bitmap: &[[u8; 8]]) -> bool {
+    /// Draws `bitmap` at (`vx`, `vy`), the parts going past the edges are
+    /// clipped, or wrapped around if `wrap` is set.
+    pub fn draw(&mut self, vx: usize, vy: usize, bitmap: &[[u8; 8]], wrap: bool) -> bool {
         let lx = vx % WIDTH;
         let ly = vy % HEIGHT;
-        let rx = (lx + 8).min(WIDTH);
-        let ry = (ly + bitmap.len()).min(HEIGHT);
 
         let mut turn_off = false;
-        for (old_row, new_row) in self.inner[ly..ry].iter_mut().zip(bitmap) {
-            for (old_bit, new_bit) in old_row[lx..rx].iter_mut().zip(new_row) {
+        for (dy, new_row) in bitmap.iter().enumerate() {
+            let y = ly + dy;
+            if y >= HEIGHT && !wrap {
+                break;
+            }
+            for (dx, new_bit) in new_row.iter().enumerate() {
+                let x = lx + dx;
+                if x >= WIDTH && !wrap {
+                    break;
+                }
+                let old_bit = &mut self.inner[y % HEIGHT][x % WIDTH];
                 if new_bit == &1 && old_bit == &1 {
                     turn_off = true;
                 }
@@ -43,26 +70,42 @@ impl Graphic {
         turn_off
     }
 
-    pub fn render(&mut self, display: &mut Display) -> Result<()> {
+    /// Presents the screen if it changed since the last render.
+    pub fn render(&mut self, frontend: &mut impl Frontend) -> Result<()> {
         // no need to render
         if !self.rerender {
             return Ok(());
         }
 
-        let point_locations = self
-            .inner
-            .iter()
-            .enumerate()
-            .flat_map(|(y, row)| {
-                row.iter()
-                    .enumerate()
-                    .filter(|(_, pixel)| pixel == &&1)
-                    .map(|(x, _)| (x, y))
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
+        let shades = self.inner.map(|row| row.map(|pixel| pixel * 255));
+
+        self.rerender = false;
+        frontend.render(&shades)
+    }
 
+    /// Renders through `filter`, this has to happen every frame since the
+    /// filter changes the picture over time.
+    pub fn render_filtered(
+        &mut self,
+        frontend: &mut impl Frontend,
+        filter: &mut Filter,
+    ) -> Result<()> {
         self.rerender = false;
-        display.render(&point_locations)
+        frontend.render(filter.apply(&self.inner))
+    }
+
+    /// Saves the screen as a PNG, each pixel `scale` times larger.
+    pub fn save_png(&self, path: &Path, palette: &Palette, scale: usize) -> Result<()> {
+        let (width, height) = (WIDTH * scale, HEIGHT * scale);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| self.inner[y / scale][x / scale]))
+            .collect::<Vec<_>>();
+        Image {
+            width,
+            height,
+            colors: &[palette.background(), palette.foreground()],
+            pixels: &pixels,
+        }
+        .save(path)
     }
 }
@@ -30,3 +30,9 @@ impl Keymap {
             .map(|(index, _)| index)
     }
 }
+
+impl From<[bool; 16]> for Keymap {
+    fn from(inner: [bool; 16]) -> Keymap {
+        Keymap { inner }
+    }
+}
@@ -1,6 +1,18 @@
+mod blocks;
+mod builder;
+pub(crate) mod buzzer;
+pub(crate) mod clock;
+mod decoded;
 pub(crate) mod graphic;
 pub(crate) mod keymap;
+mod observer;
+pub(crate) mod quirks;
+mod rng;
 mod timer;
 mod vm;
 
+pub use blocks::Backend;
+pub use builder::Chip8Builder;
+pub use observer::Observer;
+pub(crate) use vm::Exit;
 pub use vm::VM;
@@ -0,0 +1,39 @@
+use crate::opcode::Opcode;
+
+use super::VM;
+
+/// Callbacks on what the interpreter does, for profilers, tracers, coverage
+/// tools and the like, registered with [`VM::add_observer`]. Every method
+/// does nothing unless implemented, and without observers the interpreter
+/// only checks that there are none.
+///
+/// Observers are `Send` so that the VM can move to another thread, to read
+/// the results back, keep them behind an `Arc<Mutex<_>>` shared with the
+/// observer.
+#[allow(unused_variables)]
+pub trait Observer {
+    /// `opcode`, at address `pc`, is about to run.
+    fn before_instruction(&mut self, vm: &VM, pc: u16, opcode: Opcode) {}
+
+    /// `opcode`, at address `pc`, just ran.
+    fn after_instruction(&mut self, vm: &VM, pc: u16, opcode: Opcode) {}
+
+    /// An instruction stored `value` at `address`.
+    fn memory_write(&mut self, vm: &VM, address: u16, value: u8) {}
+
+    /// A sprite of `height` rows was drawn at (`x`, `y`), `collision` when
+    /// it turned off a pixel.
+    fn draw(&mut self, vm: &VM, x: u8, y: u8, height: u8, collision: bool) {}
+
+    /// The delay timer changed to `value`, set by FX15 or counting down.
+    fn delay_timer(&mut self, vm: &VM, value: u8) {}
+
+    /// The sound timer changed to `value`, set by FX18 or counting down.
+    fn sound_timer(&mut self, vm: &VM, value: u8) {}
+
+    /// FX0A started waiting for a key, to store in V`x`.
+    fn key_wait(&mut self, vm: &VM, x: usize) {}
+
+    /// The key waited for, `key`, was pressed and released.
+    fn key_wait_done(&mut self, vm: &VM, x: usize, key: u8) {}
+}
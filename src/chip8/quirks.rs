@@ -0,0 +1,163 @@
+use serde::Deserialize;
+
+/// Platforms known by the CHIP-8 database. Only the original instruction set
+/// is implemented, so the platform only selects the quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Platform {
+    OriginalChip8,
+    #[serde(rename = "hybridVIP")]
+    HybridVip,
+    ModernChip8,
+    Chip48,
+    Superchip1,
+    Superchip,
+    Xochip,
+}
+
+impl Platform {
+    /// Parses a platform id as used in the CHIP-8 database.
+    pub(crate) fn from_id(id: &str) -> Option<Platform> {
+        match id {
+            "originalChip8" => Some(Platform::OriginalChip8),
+            "hybridVIP" => Some(Platform::HybridVip),
+            "modernChip8" => Some(Platform::ModernChip8),
+            "chip48" => Some(Platform::Chip48),
+            "superchip1" => Some(Platform::Superchip1),
+            "superchip" => Some(Platform::Superchip),
+            "xochip" => Some(Platform::Xochip),
+            _ => None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+            },
+            Platform::ModernChip8 => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Chip48 | Platform::Superchip1 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Superchip => Quirks {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Xochip => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: true,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+        }
+    }
+}
+
+/// Behaviors that differ between CHIP-8 interpreters, named as in the
+/// CHIP-8 database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+    /// FX55 and FX65 increment I by X instead of X + 1.
+    pub memory_increment_by_x: bool,
+    /// FX55 and FX65 leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped.
+    pub wrap: bool,
+    /// BXNN jumps to XNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// DXYN waits for the next frame before the program continues.
+    pub vblank: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF.
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    /// The behavior of this interpreter when no platform is chosen.
+    fn default() -> Quirks {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: true,
+            vblank: false,
+            logic: true,
+        }
+    }
+}
+
+/// Quirks set individually, on top of the ones from the platform.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct QuirkOverrides {
+    pub shift: Option<bool>,
+    pub memory_increment_by_x: Option<bool>,
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub wrap: Option<bool>,
+    pub jump: Option<bool>,
+    pub vblank: Option<bool>,
+    pub logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    /// Layers `other` on top of `self`, values set in `other` win.
+    pub(crate) fn merge(&self, other: &QuirkOverrides) -> QuirkOverrides {
+        QuirkOverrides {
+            shift: other.shift.or(self.shift),
+            memory_increment_by_x: other.memory_increment_by_x.or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: other
+                .memory_leave_i_unchanged
+                .or(self.memory_leave_i_unchanged),
+            wrap: other.wrap.or(self.wrap),
+            jump: other.jump.or(self.jump),
+            vblank: other.vblank.or(self.vblank),
+            logic: other.logic.or(self.logic),
+        }
+    }
+
+    pub(crate) fn apply(&self, quirks: Quirks) -> Quirks {
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory_increment_by_x: self
+                .memory_increment_by_x
+                .unwrap_or(quirks.memory_increment_by_x),
+            memory_leave_i_unchanged: self
+                .memory_leave_i_unchanged
+                .unwrap_or(quirks.memory_leave_i_unchanged),
+            wrap: self.wrap.unwrap_or(quirks.wrap),
+            jump: self.jump.unwrap_or(quirks.jump),
+            vblank: self.vblank.unwrap_or(quirks.vblank),
+            logic: self.logic.unwrap_or(quirks.logic),
+        }
+    }
+}
@@ -1,8 +1,3 @@
-pub(super) enum TimerTick {
-    Normal,
-    SoundTimerZero,
-}
-
 #[derive(Default)]
 pub(super) struct Timer {
     pub delay: u8,
@@ -10,18 +5,8 @@ pub(super) struct Timer {
 }
 
 impl Timer {
-    pub fn tick(&mut self) -> TimerTick {
-        if self.delay > 0 {
-            self.delay -= 1;
-        }
-
-        if self.sound > 0 {
-            self.sound -= 1;
-            if self.sound == 0 {
-                return TimerTick::SoundTimerZero;
-            }
-        }
-
-        TimerTick::Normal
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
     }
 }
@@ -1,8 +1,11 @@
-use std::time::Duration;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-const FONT: [u8; 80] = [
+pub(super) const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -20,80 +23,256 @@ const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+pub(super) const MEMORY_SIZE: usize = 4096;
+
 use crate::{
+    config::{Config, Settings},
+    filter::Filter,
+    message::Message,
+    metadata::Metadata,
     opcode::Opcode,
-    ui::{audio::AudioCommand, input::PollResult, UI},
+    recorder::{self, Recorder, Wav},
+    replay::Replay,
+    ui::{
+        audio::AudioCommand,
+        input::{Hotkey, PollResult},
+        terminal::Terminal,
+        Frontend, UI,
+    },
 };
 
 use super::{
-    graphic::Graphic,
+    blocks::{Blocks, Op},
+    buzzer::Buzzer,
+    clock::Clock,
+    decoded::Decoded,
+    graphic::{Graphic, HEIGHT, WIDTH},
     keymap::Keymap,
-    timer::{Timer, TimerTick},
+    observer::Observer,
+    quirks::Quirks,
+    rng::Rng,
+    timer::Timer,
+    Backend, Chip8Builder,
 };
 
+/// Why the game stopped.
+pub(crate) enum Exit {
+    /// The window was closed.
+    Quit,
+    /// The player asked to leave the game.
+    Back,
+}
+
 enum WaitingKeyStatus {
     NoAction,
     Waiting { reg_index: usize, keymap: Keymap },
 }
 
+/// A CHIP-8 interpreter, loaded with [`VM::load`] or configured with a
+/// [`Chip8Builder`].
+///
+/// The methods reading and writing the machine state, from
+/// [`VM::registers`] to [`VM::current_instruction`], are a stable API for
+/// debuggers, test harnesses and bots: they follow semantic versioning and
+/// only change in a new major version.
 pub struct VM {
-    memory: [u8; 4096],
+    // none when loaded from bytes, there is nothing to reload then
+    rom: Option<PathBuf>,
+    content: Vec<u8>,
+    metadata: Metadata,
+    load_address: u16,
+    font_address: u16,
+    font: [u8; 80],
+    seed: u64,
+    rng: Rng,
+    memory: [u8; MEMORY_SIZE],
+    decoded: Decoded,
+    backend: Backend,
+    // only used by the blocks backend
+    blocks: Blocks,
     v: [u8; 16],
     pc: u16,
     i: u16,
     graphic: Graphic,
     stack: Vec<u16>,
-    ui: UI,
     timer: Timer,
     keymap: Keymap,
     waiting_key_status: WaitingKeyStatus,
+    clock: Clock,
+    settings: Settings,
+    quirks: Quirks,
+    tick_rate: usize,
+    // the last draw waits for the next frame, see `Quirks::vblank`
+    waiting_vblank: bool,
+    filter: Filter,
+    recorder: Option<Recorder>,
+    // the sound alone, saved to its path
+    wav: Option<(PathBuf, Wav)>,
+    buzzer: Buzzer,
+    muted: bool,
+    observers: Vec<Box<dyn Observer + Send>>,
 }
 
 impl VM {
-    pub fn load<P: AsRef<std::path::Path>>(rom: P) -> Result<VM> {
+    pub fn load<P: AsRef<Path>>(rom: P, config: &Config) -> Result<VM> {
+        let builder = Chip8Builder::new().rom_path(rom.as_ref())?;
+        let metadata = Metadata::lookup(rom.as_ref(), &builder.content, config.database.as_ref());
+        let settings = config.settings_for(rom.as_ref(), &metadata);
+        let mut chip8 = builder.settings(settings).build()?;
+        chip8.metadata = metadata;
+        Ok(chip8)
+    }
+
+    pub(super) fn new(builder: Chip8Builder) -> Result<VM> {
+        let settings = builder.settings.merge(&Settings {
+            platform: builder.platform,
+            tick_rate: builder.tick_rate,
+            ..Settings::default()
+        });
+        let seed = builder.seed.unwrap_or_else(Rng::random_seed);
         let mut chip8 = VM {
-            memory: [0; 4096],
+            rom: builder.rom,
+            content: builder.content,
+            metadata: Metadata::default(),
+            load_address: builder.load_address,
+            font_address: builder.font_address,
+            font: builder.font,
+            seed,
+            rng: Rng::new(seed),
+            memory: [0; MEMORY_SIZE],
+            decoded: Decoded::default(),
+            backend: builder.backend,
+            blocks: Blocks::default(),
             v: Default::default(),
-            pc: 0x200,
+            pc: builder.load_address,
             i: 0,
             graphic: Graphic::default(),
             stack: Vec::with_capacity(16),
-            ui: UI::new()?,
             timer: Timer::default(),
             keymap: Default::default(),
             waiting_key_status: WaitingKeyStatus::NoAction,
+            clock: Clock::default(),
+            quirks: builder.quirks.unwrap_or_else(|| settings.quirks()),
+            tick_rate: settings.tick_rate(),
+            filter: Filter::new(settings.persistence()),
+            settings,
+            waiting_vblank: false,
+            recorder: None,
+            wav: None,
+            buzzer: Buzzer::default(),
+            muted: false,
+            observers: Vec::new(),
         };
+        chip8.reset()?;
+
+        Ok(chip8)
+    }
+
+    /// Puts the machine back into its power-on state with the current content loaded.
+    fn reset(&mut self) -> Result<()> {
+        let start = self.load_address as usize;
+        if self.content.len() > self.memory.len() - start {
+            bail!(
+                "ROM is too large: {} bytes, at most {} bytes fit in memory",
+                self.content.len(),
+                self.memory.len() - start
+            );
+        }
+        let font = self.font_address as usize..self.font_address as usize + self.font.len();
+        let rom = start..start + self.content.len();
+        if font.start < rom.end && rom.start < font.end {
+            bail!(
+                "ROM at {:#05X}-{:#05X} overlaps the font at {:#05X}-{:#05X}",
+                rom.start,
+                rom.end - 1,
+                font.start,
+                font.end - 1
+            );
+        }
+
+        self.memory = [0; MEMORY_SIZE];
+        self.decoded.clear();
+        self.blocks.clear();
+        self.v = Default::default();
+        self.pc = self.load_address;
+        self.i = 0;
+        self.graphic.clear();
+        self.stack.clear();
+        self.timer = Timer::default();
+        self.waiting_key_status = WaitingKeyStatus::NoAction;
+        self.waiting_vblank = false;
+        self.filter = Filter::new(self.settings.persistence());
+        // the same numbers again after a reset
+        self.rng = Rng::new(self.seed);
 
         // load font
-        chip8.memory[..80].clone_from_slice(&FONT);
+        let font_address = self.font_address as usize;
+        self.memory[font_address..font_address + self.font.len()].clone_from_slice(&self.font);
 
         // load content
-        let content = std::fs::read(rom.as_ref())?;
-        let start = 0x200;
-        chip8.memory[start..start + content.len()].clone_from_slice(&content);
+        self.memory[start..start + self.content.len()].clone_from_slice(&self.content);
 
-        Ok(chip8)
+        Ok(())
     }
 
-    fn fetch_opcode(&mut self) -> Result<Opcode> {
-        let first = *self
-            .memory
-            .get(self.pc as usize)
-            .context("Invalid memory access")? as u16;
-        let second = *self
-            .memory
-            .get(self.pc as usize + 1)
-            .context("Invalid memory access")? as u16;
-
-        let raw = (first << 8) | second;
-        let opcode = Opcode::try_from(raw)?;
+    /// Reads the ROM file from disk again and restarts it.
+    fn reload(&mut self) -> Result<()> {
+        let Some(rom) = &self.rom else {
+            return self.reset();
+        };
+        let content =
+            std::fs::read(rom).with_context(|| format!("Cannot read {}", rom.display()))?;
+        let previous = std::mem::replace(&mut self.content, content);
+        if let Err(err) = self.reset() {
+            self.content = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
 
+    fn fetch_opcode(&mut self) -> Result<Opcode> {
+        let opcode = match self.decoded.get(self.pc) {
+            Some(opcode) => opcode,
+            None => {
+                let opcode = self.current_instruction()?;
+                self.decoded.insert(self.pc, opcode);
+                opcode
+            }
+        };
         self.pc += 2;
         Ok(opcode)
     }
 
     fn execute(&mut self) -> Result<()> {
+        let pc = self.pc;
         let opcode = self.fetch_opcode().context("Opcode should not be None")?;
+        self.run_instruction(pc, opcode)
+    }
+
+    /// Runs `opcode`, found at `pc`, the program counter already past it.
+    fn run_instruction(&mut self, pc: u16, opcode: Opcode) -> Result<()> {
+        self.notify(|observer, vm| observer.before_instruction(vm, pc, opcode));
+        self.run_opcode(opcode)?;
+        self.notify(|observer, vm| observer.after_instruction(vm, pc, opcode));
+        Ok(())
+    }
+
+    /// The block of instructions starting at the program counter, translated
+    /// on first use, as indices for `Blocks::op`.
+    fn fetch_block(&mut self) -> Result<Range<usize>> {
+        if let Some(block) = self.blocks.get(self.pc) {
+            return Ok(block);
+        }
+        let quirks = self.quirks;
+        self.blocks
+            .translate(self.pc, |address| {
+                decode(&self.memory, address).map(|opcode| bind(opcode, &quirks))
+            })
+            .context("Opcode should not be None")
+    }
+
+    fn run_opcode(&mut self, opcode: Opcode) -> Result<()> {
         match opcode {
             Opcode::ClearScreen => {
                 self.graphic.clear();
@@ -135,15 +314,21 @@ impl VM {
             }
             Opcode::AssignOr { x, y } => {
                 self.v[x] |= self.v[y];
-                self.v[0xF] = 0;
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
+                }
             }
             Opcode::AssignAnd { x, y } => {
                 self.v[x] &= self.v[y];
-                self.v[0xF] = 0;
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
+                }
             }
             Opcode::AssignXor { x, y } => {
                 self.v[x] ^= self.v[y];
-                self.v[0xF] = 0;
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
+                }
             }
             Opcode::AssignAdd { x, y } => {
                 let res = (self.v[x] as u16) + (self.v[y] as u16);
@@ -163,10 +348,14 @@ impl VM {
                     self.v[0xF] = 0;
                 }
             }
-            Opcode::AssignShift { x } => {
-                let lsb = self.v[x] & 1;
-                self.v[x] >>= 1;
-                self.v[0xF] = lsb;
+            Opcode::AssignShift { x, y } => {
+                let source = if self.quirks.shift {
+                    self.v[x]
+                } else {
+                    self.v[y]
+                };
+                self.v[x] = source >> 1;
+                self.v[0xF] = source & 1;
             }
             Opcode::AssignRevSub { x, y } => {
                 if self.v[y] >= self.v[x] {
@@ -177,10 +366,14 @@ impl VM {
                     self.v[0xF] = 0;
                 }
             }
-            Opcode::AssignRevShift { x } => {
-                let msb = (self.v[x] & 0x80) >> 7;
-                self.v[x] = (((self.v[x] as u16) << 1) & 0xFF) as u8;
-                self.v[0xF] = msb;
+            Opcode::AssignRevShift { x, y } => {
+                let source = if self.quirks.shift {
+                    self.v[x]
+                } else {
+                    self.v[y]
+                };
+                self.v[x] = (((source as u16) << 1) & 0xFF) as u8;
+                self.v[0xF] = (source & 0x80) >> 7;
             }
             Opcode::SkipIfNotEqual { x, y } => {
                 if self.v[x] != self.v[y] {
@@ -190,11 +383,16 @@ impl VM {
             Opcode::RegAssign { addr } => {
                 self.i = addr;
             }
-            Opcode::JumpOffset { x, addr } => {
-                self.pc = addr + self.v[x] as u16;
+            Opcode::JumpOffset { addr } => {
+                let offset = self.v[jump_offset_register(addr, &self.quirks)];
+                self.pc = addr + offset as u16;
+            }
+            Opcode::Random { x, nn } => {
+                self.v[x] = self.rng.next_u8() & nn;
             }
             Opcode::Draw { x, y, height } => {
-                let bitmap = self.memory[self.i as usize..(self.i + height as u16) as usize]
+                let sprite = self.memory_at_i(height as usize)?;
+                let bitmap = self.memory[sprite]
                     .iter()
                     .map(|row| {
                         (0..8)
@@ -210,19 +408,24 @@ impl VM {
 
                 let vx = self.v[x] as usize;
                 let vy = self.v[y] as usize;
-                let turned_off = self.graphic.draw(vx, vy, &bitmap);
+                let turned_off = self.graphic.draw(vx, vy, &bitmap, self.quirks.wrap);
                 if turned_off {
                     self.v[0xF] = 1;
                 }
+                self.notify(|observer, vm| {
+                    observer.draw(vm, vx as u8, vy as u8, height, turned_off)
+                });
+                self.waiting_vblank = self.quirks.vblank;
             }
+            // there are 16 keys, the VIP only looks at the low nibble of VX
             Opcode::SkipIfPress { x } => {
-                let vx = self.v[x];
+                let vx = self.v[x] & 0xF;
                 if self.keymap.is_down(vx as usize) {
                     self.pc += 2;
                 }
             }
             Opcode::SkipIfNotPress { x } => {
-                let vx = self.v[x];
+                let vx = self.v[x] & 0xF;
                 if !self.keymap.is_down(vx as usize) {
                     self.pc += 2;
                 }
@@ -235,75 +438,462 @@ impl VM {
                     reg_index: x,
                     keymap: Keymap::default(),
                 };
+                self.notify(|observer, vm| observer.key_wait(vm, x));
             }
             Opcode::DelayTimerAssign { x } => {
                 self.timer.delay = self.v[x];
+                self.notify(|observer, vm| observer.delay_timer(vm, vm.timer.delay));
             }
             Opcode::SoundTimerAssign { x } => {
                 self.timer.sound = self.v[x];
+                self.notify(|observer, vm| observer.sound_timer(vm, vm.timer.sound));
             }
             Opcode::RegAssignAdd { x } => {
-                self.i += self.v[x] as u16;
+                self.i = self.i.wrapping_add(self.v[x] as u16);
             }
             Opcode::RegAssignFont { x } => {
-                let vx = self.v[x];
-                debug_assert!(vx < 16);
-                self.i = (vx * 5) as u16;
+                // as for keys, only the low nibble picks a character
+                let vx = self.v[x] & 0xF;
+                self.i = self.font_address + vx as u16 * 5;
             }
             Opcode::BinaryCodedDecimal { x } => {
                 let vx = self.v[x];
-                self.memory[self.i as usize] = vx / 100;
-                self.memory[self.i as usize + 1] = (vx / 10) % 10;
-                self.memory[self.i as usize + 2] = vx % 10;
+                let start = self.memory_at_i(3)?.start;
+                self.store(start, vx / 100);
+                self.store(start + 1, (vx / 10) % 10);
+                self.store(start + 2, vx % 10);
             }
             Opcode::RegDump { x } => {
+                let start = self.memory_at_i(x + 1)?.start;
                 for offset in 0..=x {
-                    self.memory[self.i as usize + offset] = self.v[offset];
+                    self.store(start + offset, self.v[offset]);
                 }
-                self.i += x as u16 + 1;
+                self.increment_i_after_memory_access(x);
             }
             Opcode::RegLoad { x } => {
+                let start = self.memory_at_i(x + 1)?.start;
                 for offset in 0..=x {
-                    self.v[offset] = self.memory[self.i as usize + offset];
+                    self.v[offset] = self.memory[start + offset];
                 }
-                self.i += x as u16 + 1;
+                self.increment_i_after_memory_access(x);
             }
         }
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result<()> {
-        loop {
-            match self.ui.input.poll() {
-                PollResult::Stop => {
-                    break;
+    /// The `length` bytes from I an instruction reads or writes, an error
+    /// when they go past the end of memory.
+    fn memory_at_i(&self, length: usize) -> Result<Range<usize>> {
+        let start = self.i as usize;
+        if start + length > MEMORY_SIZE {
+            bail!("Cannot access {} bytes at I = {:#05X}", length, self.i);
+        }
+        Ok(start..start + length)
+    }
+
+    /// Writes memory for an instruction.
+    fn store(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+        self.decoded.invalidate(address);
+        self.blocks.invalidate(address);
+        self.notify(|observer, vm| observer.memory_write(vm, address as u16, value));
+    }
+
+    /// Calls every observer, `vm` is the current state.
+    #[inline]
+    fn notify(&mut self, event: impl Fn(&mut dyn Observer, &VM)) {
+        if self.observers.is_empty() {
+            return;
+        }
+        // observers see the VM while being borrowed from it
+        let mut observers = std::mem::take(&mut self.observers);
+        for observer in &mut observers {
+            event(observer.as_mut(), self);
+        }
+        self.observers = observers;
+    }
+
+    fn increment_i_after_memory_access(&mut self, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        if self.quirks.memory_increment_by_x {
+            self.i = self.i.wrapping_add(x as u16);
+        } else {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    /// Runs the game in a window, `report` tells the player what happened
+    /// along the way.
+    pub fn run(&mut self, mut report: impl FnMut(Message)) -> Result<()> {
+        let mut ui = UI::new(&self.settings)?;
+        self.play(&mut ui, &mut report)?;
+        Ok(())
+    }
+
+    /// Runs the game in the terminal, without a window.
+    pub fn run_in_terminal(&mut self, report: impl FnMut(Message)) -> Result<()> {
+        // printing would garble the screen, messages wait until the
+        // terminal is restored
+        let mut messages = Vec::new();
+        let result = Terminal::new().and_then(|mut terminal| {
+            self.play(&mut terminal, &mut |message| messages.push(message))
+        });
+        messages.into_iter().for_each(report);
+        result.map(|_| ())
+    }
+
+    /// Runs the game on `ui` until the player leaves it.
+    pub(crate) fn play(
+        &mut self,
+        ui: &mut impl Frontend,
+        report: &mut impl FnMut(Message),
+    ) -> Result<Exit> {
+        ui.setup(&self.settings)?;
+        for warning in ui.take_warnings() {
+            report(Message::Warning(warning));
+        }
+        self.graphic.invalidate();
+        self.update_title(ui)?;
+        // the frontend may come from a previous game
+        self.update_audio(ui);
+
+        let exit = self.play_frames(ui, report);
+
+        ui.audio(AudioCommand::Pause);
+        // keep what was recorded up to an error too
+        if let Some(path) = self.stop_recording()? {
+            report(Message::Info(format!(
+                "Saved recording to {}",
+                path.display()
+            )));
+        }
+        exit
+    }
+
+    fn play_frames(
+        &mut self,
+        ui: &mut impl Frontend,
+        report: &mut impl FnMut(Message),
+    ) -> Result<Exit> {
+        let exit = loop {
+            let poll = ui.poll();
+            for warning in ui.take_warnings() {
+                report(Message::Warning(warning));
+            }
+            match poll {
+                PollResult::Quit => break Exit::Quit,
+                PollResult::Back => break Exit::Back,
+                PollResult::Keymap { keymap, hotkeys } => {
+                    // keys are still updated while paused, so the next
+                    // advanced frame sees them
+                    self.keymap = keymap;
+                    if !hotkeys.is_empty() {
+                        self.handle_hotkeys(ui, hotkeys, report)?;
+                    }
                 }
-                PollResult::Keymap(keymap) => self.keymap = keymap,
             };
 
-            let wait_key_done = self.wait_key();
-            if wait_key_done {
-                self.execute()?;
+            if self.clock.frame_due() {
+                self.play_frame(ui)?;
+            } else {
+                // paused, only redraw after a reset or a resize
+                self.graphic.render(ui)?;
             }
 
-            self.graphic.render(&mut self.ui.display)?;
+            self.clock.wait();
+        };
+
+        Ok(exit)
+    }
 
-            if matches!(self.timer.tick(), TimerTick::SoundTimerZero) {
-                self.ui.audio.run(AudioCommand::Resume);
+    /// Runs `frames` frames without a window, pressing the keys of `replay`.
+    pub fn run_headless(&mut self, frames: u64, replay: &Replay) -> Result<()> {
+        for _ in 0..frames {
+            self.keymap = replay.keymap(self.clock.frames());
+            // never paused, this only counts the frame
+            self.clock.frame_due();
+            self.step_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Records every following frame to the recordings directory, and
+    /// returns the path of the files without extension.
+    pub fn start_recording(&mut self) -> Result<PathBuf> {
+        self.stop_recording()?;
+        let name = format!("{}-{:06}", self.name(), self.clock.frames());
+        let path = self.settings.recording_dir().join(name);
+        self.recorder = Some(Recorder::create(
+            &path,
+            &self.settings.palette(),
+            &self.settings.beeper,
+            self.settings.recording_scale(),
+        )?);
+        Ok(path)
+    }
+
+    /// Completes the recording, if any, and returns the path of its files
+    /// without extension.
+    pub fn stop_recording(&mut self) -> Result<Option<PathBuf>> {
+        self.recorder.take().map(Recorder::finish).transpose()
+    }
+
+    /// Saves the sound of every following frame to the WAV file `path`.
+    pub fn start_wav(&mut self, path: &Path) -> Result<()> {
+        self.stop_wav()?;
+        let wav = Wav::create(recorder::create_file(path)?, &self.settings.beeper)?;
+        self.wav = Some((path.to_path_buf(), wav));
+        Ok(())
+    }
+
+    /// Completes the WAV file, if any, and returns its path.
+    pub fn stop_wav(&mut self) -> Result<Option<PathBuf>> {
+        let Some((path, wav)) = self.wav.take() else {
+            return Ok(None);
+        };
+        wav.finish()?;
+        Ok(Some(path))
+    }
+
+    /// Name of the ROM, to name the files saved from it.
+    fn name(&self) -> String {
+        self.rom
+            .as_ref()
+            .and_then(|rom| rom.file_stem())
+            .map_or("chip8".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            })
+    }
+
+    /// Saves the screen as a PNG in the screenshots directory, and returns
+    /// its path.
+    pub fn save_screenshot(&self) -> Result<PathBuf> {
+        let name = format!("{}-{:06}.png", self.name(), self.clock.frames());
+        let path = self.settings.screenshot_dir().join(name);
+        self.graphic.save_png(
+            &path,
+            &self.settings.palette(),
+            self.settings.screenshot_scale(),
+        )?;
+        Ok(path)
+    }
+
+    fn play_frame(&mut self, ui: &mut impl Frontend) -> Result<()> {
+        self.step_frame()?;
+
+        // the screen is shown once per frame, whatever the host refresh rate
+        if self.clock.render_due() {
+            if self.filter.enabled() {
+                self.graphic.render_filtered(ui, &mut self.filter)?;
+            } else {
+                self.graphic.render(ui)?;
             }
-            self.ui.audio.run(AudioCommand::TryPause);
+        }
 
-            std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 1000));
+        ui.play_sound(&self.buzzer);
+
+        Ok(())
+    }
+
+    /// Holds down the CHIP-8 keys set in `keys`, indexed 0 to F, for the
+    /// next frames.
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keymap = Keymap::from(keys);
+    }
+
+    /// Emulates one frame, without a window: the instructions of the frame,
+    /// then the timers. Fails on words that are no instruction, such as
+    /// 5XY1 or 9XY1, leaving the rest of the frame unrun.
+    pub fn step_frame(&mut self) -> Result<()> {
+        self.waiting_vblank = false;
+        self.buzzer.begin(self.timer.sound > 0);
+        if self.backend == Backend::Blocks {
+            self.run_blocks()?;
+        } else {
+            for tick in 0..self.tick_rate {
+                let wait_key_done = self.wait_key();
+                if wait_key_done {
+                    self.execute()?;
+                }
+                self.tick_done(tick);
+
+                if self.waiting_vblank {
+                    break;
+                }
+            }
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(self.graphic.pixels(), &self.buzzer)?;
+        }
+        if let Some((_, wav)) = &mut self.wav {
+            wav.frame(&self.buzzer)?;
         }
 
+        let (delay, sound) = (self.timer.delay, self.timer.sound);
+        self.timer.tick();
+        if self.timer.delay != delay {
+            self.notify(|observer, vm| observer.delay_timer(vm, vm.timer.delay));
+        }
+        if self.timer.sound != sound {
+            self.notify(|observer, vm| observer.sound_timer(vm, vm.timer.sound));
+        }
+        Ok(())
+    }
+
+    /// Runs the ticks of a frame a block at a time, the same as the
+    /// interpreter loop of `step_frame`.
+    fn run_blocks(&mut self) -> Result<()> {
+        let mut tick = 0;
+        while tick < self.tick_rate {
+            if !self.wait_key() {
+                self.tick_done(tick);
+                tick += 1;
+                continue;
+            }
+            // only the last instruction of a block can draw, start a key
+            // wait or change the sound timer, the checks between
+            // instructions of the interpreter can wait until the block ends
+            let block = self.fetch_block()?;
+            let end = block.end.min(block.start + self.tick_rate - tick);
+            if self.observers.is_empty() {
+                for index in block.start..end {
+                    let op = self.blocks.op(index);
+                    self.pc += 2;
+                    (op.run)(self, op)?;
+                }
+            } else {
+                for index in block.start..end {
+                    let op = self.blocks.op(index);
+                    let (pc, opcode) = (self.pc, op.opcode);
+                    self.notify(|observer, vm| observer.before_instruction(vm, pc, opcode));
+                    self.pc += 2;
+                    (op.run)(self, op)?;
+                    self.notify(|observer, vm| observer.after_instruction(vm, pc, opcode));
+                }
+            }
+            tick += end - block.start;
+            self.tick_done(tick - 1);
+
+            if self.waiting_vblank {
+                break;
+            }
+        }
         Ok(())
     }
 
+    /// Updates the buzzer after `tick`, counting from 0.
+    fn tick_done(&mut self, tick: usize) {
+        let on = self.timer.sound > 0;
+        if on != self.buzzer.on() {
+            // instructions are spread evenly over the frame
+            let position = (tick + 1) as f32 / self.tick_rate as f32;
+            self.buzzer.set(on, position);
+        }
+    }
+
+    fn handle_hotkeys(
+        &mut self,
+        ui: &mut impl Frontend,
+        hotkeys: Vec<Hotkey>,
+        report: &mut impl FnMut(Message),
+    ) -> Result<()> {
+        for hotkey in hotkeys {
+            match hotkey {
+                Hotkey::TogglePause => self.clock.toggle_pause(),
+                Hotkey::AdvanceFrame => self.clock.advance(),
+                Hotkey::Reset => {
+                    self.reset()?;
+                    self.graphic.invalidate();
+                }
+                Hotkey::Reload => match self.reload() {
+                    Ok(()) => self.graphic.invalidate(),
+                    // keep running the previous version, the file may be half written
+                    Err(err) => report(Message::Warning(format!("Cannot reload ROM: {:#}", err))),
+                },
+                Hotkey::Speed(speed) => {
+                    self.clock.set_speed(speed);
+                    self.update_audio(ui);
+                }
+                Hotkey::ToggleFullscreen => ui.toggle_fullscreen()?,
+                Hotkey::ToggleRecording if self.recorder.is_some() => match self.stop_recording() {
+                    Ok(path) => report(Message::Info(format!(
+                        "Saved recording to {}",
+                        path.unwrap_or_default().display()
+                    ))),
+                    Err(err) => report(Message::Warning(format!(
+                        "Cannot save recording: {:#}",
+                        err
+                    ))),
+                },
+                Hotkey::ToggleRecording => match self.start_recording() {
+                    Ok(path) => report(Message::Info(format!("Recording to {}", path.display()))),
+                    Err(err) => report(Message::Warning(format!(
+                        "Cannot start recording: {:#}",
+                        err
+                    ))),
+                },
+                Hotkey::Screenshot => match self.save_screenshot() {
+                    Ok(path) => report(Message::Info(format!(
+                        "Saved screenshot to {}",
+                        path.display()
+                    ))),
+                    Err(err) => report(Message::Warning(format!(
+                        "Cannot save screenshot: {:#}",
+                        err
+                    ))),
+                },
+                Hotkey::Redraw => self.graphic.invalidate(),
+                Hotkey::FastForward(fast_forward) => {
+                    self.clock.set_fast_forward(fast_forward);
+                    self.update_audio(ui);
+                }
+                Hotkey::ToggleMute => {
+                    self.muted = !self.muted;
+                    self.update_audio(ui);
+                }
+            }
+        }
+
+        self.update_title(ui)
+    }
+
+    /// Silences the buzzer while muted or fast-forwarding.
+    fn update_audio(&self, ui: &mut impl Frontend) {
+        ui.audio(if self.muted || self.clock.fast_forwarding() {
+            AudioCommand::Mute
+        } else {
+            AudioCommand::Unmute
+        });
+        ui.audio(AudioCommand::Speed(self.clock.speed()));
+    }
+
+    fn update_title(&self, ui: &mut impl Frontend) -> Result<()> {
+        let recording = self.recorder.as_ref().map(|_| "recording".to_string());
+        let muted = self.muted.then(|| "muted".to_string());
+        let status = self
+            .clock
+            .status()
+            .into_iter()
+            .chain(recording)
+            .chain(muted)
+            .collect::<Vec<_>>();
+        let title = match status.is_empty() {
+            true => "chip8".to_string(),
+            false => format!("chip8 [{}]", status.join(", ")),
+        };
+        ui.set_title(&title)
+    }
+
     fn wait_key(&mut self) -> bool {
         if let WaitingKeyStatus::Waiting { reg_index, keymap } = &mut self.waiting_key_status {
             if let Some(key_id) = keymap.down_to_up(&self.keymap) {
-                self.v[*reg_index] = key_id as u8;
+                let x = *reg_index;
+                self.v[x] = key_id as u8;
                 self.waiting_key_status = WaitingKeyStatus::NoAction;
+                self.notify(|observer, vm| observer.key_wait_done(vm, x, key_id as u8));
             } else {
                 *keymap = keymap.or(&self.keymap);
             }
@@ -312,3 +902,361 @@ impl VM {
         matches!(self.waiting_key_status, WaitingKeyStatus::NoAction)
     }
 }
+
+// the stable API, see the documentation of `VM`
+impl VM {
+    /// What the ROM database knows about the ROM given to `load`.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Registers V0 to VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.v
+    }
+
+    /// Address of the next instruction.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// The address register I.
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    /// Return addresses of the subroutines being run, the innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn set_stack(&mut self, stack: Vec<u16>) {
+        self.stack = stack;
+    }
+
+    /// The whole 4 KB of memory.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// `length` bytes of memory from `address`.
+    pub fn read_memory(&self, address: u16, length: usize) -> Result<&[u8]> {
+        let start = address as usize;
+        start
+            .checked_add(length)
+            .and_then(|end| self.memory.get(start..end))
+            .with_context(|| format!("Cannot read {} bytes at {:#05X}", length, address))
+    }
+
+    /// Copies `bytes` to memory from `address`.
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) -> Result<()> {
+        let start = address as usize;
+        let end = start
+            .checked_add(bytes.len())
+            .filter(|end| *end <= MEMORY_SIZE)
+            .with_context(|| format!("Cannot write {} bytes at {:#05X}", bytes.len(), address))?;
+        self.memory[start..end].copy_from_slice(bytes);
+        for address in start..end {
+            self.decoded.invalidate(address);
+            self.blocks.invalidate(address);
+        }
+        Ok(())
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.timer.delay
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.timer.delay = value;
+    }
+
+    /// The buzzer sounds while the sound timer is above 0.
+    pub fn sound_timer(&self) -> u8 {
+        self.timer.sound
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.timer.sound = value;
+    }
+
+    /// The buzzer during the last frame.
+    pub fn buzzer(&self) -> &Buzzer {
+        &self.buzzer
+    }
+
+    /// The screen, [`SCREEN_HEIGHT`](crate::SCREEN_HEIGHT) rows of
+    /// [`SCREEN_WIDTH`](crate::SCREEN_WIDTH) pixels, 1 when lit and 0
+    /// otherwise.
+    pub fn framebuffer(&self) -> &[[u8; WIDTH]; HEIGHT] {
+        self.graphic.pixels()
+    }
+
+    /// Lights the pixel at (`x`, `y`) or turns it off, coordinates past the
+    /// edges wrap around.
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        self.graphic.set_pixel(x, y, lit);
+    }
+
+    /// Whether FX0A is waiting for a key press and release.
+    pub fn is_waiting_for_key(&self) -> bool {
+        matches!(self.waiting_key_status, WaitingKeyStatus::Waiting { .. })
+    }
+
+    /// Registers `observer`, called on every following instruction.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer + Send>) {
+        self.observers.push(observer);
+    }
+
+    /// Unregisters every observer.
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// The instruction at the program counter, run next.
+    pub fn current_instruction(&self) -> Result<Opcode> {
+        self.instruction_at(self.pc)
+    }
+
+    /// The instruction at `address`.
+    pub fn instruction_at(&self, address: u16) -> Result<Opcode> {
+        decode(&self.memory, address)
+    }
+}
+
+/// Decodes the instruction at `address` in `memory`.
+fn decode(memory: &[u8], address: u16) -> Result<Opcode> {
+    let start = address as usize;
+    let raw = memory
+        .get(start..start + 2)
+        .with_context(|| format!("Cannot read 2 bytes at {:#05X}", address))
+        .context("Invalid memory access")?;
+    Opcode::try_from(u16::from_be_bytes([raw[0], raw[1]]))
+}
+
+/// Binds `opcode` to its handler for the blocks backend. The instructions
+/// not worth a handler of their own run as the interpreter runs them.
+/// The register JumpOffset adds to `addr`, VX with the jump quirk, X being
+/// the top nibble of `addr`, V0 otherwise.
+fn jump_offset_register(addr: u16, quirks: &Quirks) -> usize {
+    if quirks.jump {
+        (addr >> 8) as usize & 0xF
+    } else {
+        0
+    }
+}
+
+fn bind(opcode: Opcode, quirks: &Quirks) -> Op {
+    let op = |run: fn(&mut VM, Op) -> Result<()>, x: usize, y: usize, n: u16| Op {
+        run,
+        x,
+        y,
+        n,
+        opcode,
+    };
+    match opcode {
+        Opcode::Return => op(VM::op_return, 0, 0, 0),
+        Opcode::Jump { addr } => op(VM::op_jump, 0, 0, addr),
+        Opcode::CallSub { addr } => op(VM::op_call, 0, 0, addr),
+        Opcode::SkipIfEqualConst { x, nn } => op(VM::op_skip_eq_const, x, 0, nn as u16),
+        Opcode::SkipIfNotEqualConst { x, nn } => op(VM::op_skip_ne_const, x, 0, nn as u16),
+        Opcode::SkipIfEqual { x, y } => op(VM::op_skip_eq, x, y, 0),
+        Opcode::SkipIfNotEqual { x, y } => op(VM::op_skip_ne, x, y, 0),
+        Opcode::SetConst { x, nn } => op(VM::op_set_const, x, 0, nn as u16),
+        Opcode::AddConst { x, nn } => op(VM::op_add_const, x, 0, nn as u16),
+        Opcode::Assign { x, y } => op(VM::op_assign, x, y, 0),
+        Opcode::AssignOr { x, y } if quirks.logic => op(VM::op_or_reset_flag, x, y, 0),
+        Opcode::AssignOr { x, y } => op(VM::op_or, x, y, 0),
+        Opcode::AssignAnd { x, y } if quirks.logic => op(VM::op_and_reset_flag, x, y, 0),
+        Opcode::AssignAnd { x, y } => op(VM::op_and, x, y, 0),
+        Opcode::AssignXor { x, y } if quirks.logic => op(VM::op_xor_reset_flag, x, y, 0),
+        Opcode::AssignXor { x, y } => op(VM::op_xor, x, y, 0),
+        Opcode::AssignAdd { x, y } => op(VM::op_add, x, y, 0),
+        Opcode::AssignSub { x, y } => op(VM::op_sub, x, y, 0),
+        Opcode::AssignRevSub { x, y } => op(VM::op_rev_sub, x, y, 0),
+        // the shift quirk shifts VX in place, Y is the source register
+        Opcode::AssignShift { x, y } => {
+            op(VM::op_shift_right, x, if quirks.shift { x } else { y }, 0)
+        }
+        Opcode::AssignRevShift { x, y } => {
+            op(VM::op_shift_left, x, if quirks.shift { x } else { y }, 0)
+        }
+        Opcode::RegAssign { addr } => op(VM::op_set_i, 0, 0, addr),
+        Opcode::RegAssignAdd { x } => op(VM::op_add_i, x, 0, 0),
+        // X is the offset register
+        Opcode::JumpOffset { addr } => op(
+            VM::op_jump_offset,
+            jump_offset_register(addr, quirks),
+            0,
+            addr,
+        ),
+        Opcode::AssignDelayTimer { x } => op(VM::op_get_delay, x, 0, 0),
+        _ => op(VM::op_interpret, 0, 0, 0),
+    }
+}
+
+// handlers of the blocks backend, they behave exactly like `run_opcode`
+impl VM {
+    fn op_interpret(&mut self, op: Op) -> Result<()> {
+        self.run_opcode(op.opcode)
+    }
+
+    fn op_return(&mut self, _: Op) -> Result<()> {
+        self.pc = self.stack.pop().context("Invalid stack pointer")?;
+        Ok(())
+    }
+
+    fn op_jump(&mut self, op: Op) -> Result<()> {
+        self.pc = op.n;
+        Ok(())
+    }
+
+    fn op_jump_offset(&mut self, op: Op) -> Result<()> {
+        self.pc = op.n + self.v[op.x] as u16;
+        Ok(())
+    }
+
+    fn op_call(&mut self, op: Op) -> Result<()> {
+        self.stack.push(self.pc);
+        self.pc = op.n;
+        Ok(())
+    }
+
+    fn op_skip_eq_const(&mut self, op: Op) -> Result<()> {
+        if self.v[op.x] == op.n as u8 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn op_skip_ne_const(&mut self, op: Op) -> Result<()> {
+        if self.v[op.x] != op.n as u8 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn op_skip_eq(&mut self, op: Op) -> Result<()> {
+        if self.v[op.x] == self.v[op.y] {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn op_skip_ne(&mut self, op: Op) -> Result<()> {
+        if self.v[op.x] != self.v[op.y] {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn op_set_const(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] = op.n as u8;
+        Ok(())
+    }
+
+    fn op_add_const(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] = self.v[op.x].wrapping_add(op.n as u8);
+        Ok(())
+    }
+
+    fn op_assign(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] = self.v[op.y];
+        Ok(())
+    }
+
+    fn op_or(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] |= self.v[op.y];
+        Ok(())
+    }
+
+    fn op_or_reset_flag(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] |= self.v[op.y];
+        self.v[0xF] = 0;
+        Ok(())
+    }
+
+    fn op_and(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] &= self.v[op.y];
+        Ok(())
+    }
+
+    fn op_and_reset_flag(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] &= self.v[op.y];
+        self.v[0xF] = 0;
+        Ok(())
+    }
+
+    fn op_xor(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] ^= self.v[op.y];
+        Ok(())
+    }
+
+    fn op_xor_reset_flag(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] ^= self.v[op.y];
+        self.v[0xF] = 0;
+        Ok(())
+    }
+
+    fn op_add(&mut self, op: Op) -> Result<()> {
+        let (sum, carry) = self.v[op.x].overflowing_add(self.v[op.y]);
+        self.v[op.x] = sum;
+        self.v[0xF] = carry as u8;
+        Ok(())
+    }
+
+    fn op_sub(&mut self, op: Op) -> Result<()> {
+        let (vx, vy) = (self.v[op.x], self.v[op.y]);
+        self.v[op.x] = vx.wrapping_sub(vy);
+        self.v[0xF] = (vx >= vy) as u8;
+        Ok(())
+    }
+
+    fn op_rev_sub(&mut self, op: Op) -> Result<()> {
+        let (vx, vy) = (self.v[op.x], self.v[op.y]);
+        self.v[op.x] = vy.wrapping_sub(vx);
+        self.v[0xF] = (vy >= vx) as u8;
+        Ok(())
+    }
+
+    fn op_shift_right(&mut self, op: Op) -> Result<()> {
+        let source = self.v[op.y];
+        self.v[op.x] = source >> 1;
+        self.v[0xF] = source & 1;
+        Ok(())
+    }
+
+    fn op_shift_left(&mut self, op: Op) -> Result<()> {
+        let source = self.v[op.y];
+        self.v[op.x] = source << 1;
+        self.v[0xF] = source >> 7;
+        Ok(())
+    }
+
+    fn op_set_i(&mut self, op: Op) -> Result<()> {
+        self.i = op.n;
+        Ok(())
+    }
+
+    fn op_add_i(&mut self, op: Op) -> Result<()> {
+        self.i = self.i.wrapping_add(self.v[op.x] as u16);
+        Ok(())
+    }
+
+    fn op_get_delay(&mut self, op: Op) -> Result<()> {
+        self.v[op.x] = self.timer.delay;
+        Ok(())
+    }
+}
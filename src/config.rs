@@ -0,0 +1,466 @@
+//! User configuration, loaded from a TOML file.
+//!
+//! ```toml
+//! # built-in layout the key map starts from
+//! layout = "azerty"
+//!
+//! # interpreter behavior, the quirks below override the ones of the platform
+//! platform = "originalChip8"
+//! tick_rate = 15
+//!
+//! # colors, set individually below on top of the theme
+//! theme = "amber"
+//!
+//! # no sound device is opened with audio = false
+//! audio = true
+//!
+//! # reduce flicker with "fade" or "max"
+//! persistence = "fade"
+//!
+//! # initial window size, and "integer" or "fit" scaling when resized
+//! scale = 10
+//! scaling = "fit"
+//!
+//! # where F12 saves screenshots, and how much larger than the screen they are
+//! screenshot_dir = "screenshots"
+//! screenshot_scale = 4
+//!
+//! # where F10 saves GIF, Y4M and WAV recordings, and their size
+//! recording_dir = "recordings"
+//! recording_scale = 4
+//!
+//! # terminal frontend: "half-block" or "braille" characters, and how the
+//! # buzzer shows, "sound" or "flash"
+//! glyphs = "braille"
+//! bell = "flash"
+//!
+//! # directory of the CHIP-8 community database
+//! rom_db = "chip-8-database/database"
+//!
+//! # host keys bound to a CHIP-8 key, replacing the ones from the layout
+//! [keys]
+//! 5 = ["Z", "Space"]
+//!
+//! # game controller buttons bound to a CHIP-8 key
+//! [buttons]
+//! 5 = ["a", "b"]
+//!
+//! [quirks]
+//! shift = true
+//!
+//! [palette]
+//! background = "#000000"
+//! foreground = "#ffb000"
+//!
+//! # sound of the buzzer, the waveform is "square", "pulse", "triangle" or "sine"
+//! [beeper]
+//! frequency = 440
+//! waveform = "triangle"
+//! volume = 0.5
+//!
+//! # overrides for a single ROM, keyed by its file name or SHA-1
+//! [roms."Blitz [David Winter].ch8".keys]
+//! 5 = ["Down"]
+//! ```
+//!
+//! Top-level keys come before the first table, TOML puts any key after a
+//! table header into that table. Unknown keys are rejected.
+//!
+//! Host keys use SDL key names, such as `W`, `Space`, `Left` or `Keypad 8`.
+//! Controller buttons use SDL game controller button names, such as `a`,
+//! `x`, `dpup` or `leftshoulder`. The palette also takes `plane2` and
+//! `blend`, the colors of the second XO-CHIP plane and of both planes.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    beeper::BeeperSettings,
+    chip8::quirks::{Platform, QuirkOverrides, Quirks},
+    filter::Persistence,
+    metadata::{Metadata, RomDatabase},
+    palette::{Palette, PaletteOverrides, Theme},
+    ui::{Bell, Glyphs, Scaling},
+};
+
+const DEFAULT_CONFIG: &str = "chip8.toml";
+const DEFAULT_TICK_RATE: usize = 16;
+const DEFAULT_SCALE: u32 = 10;
+const DEFAULT_SCREENSHOT_DIR: &str = "screenshots";
+const DEFAULT_RECORDING_DIR: &str = "recordings";
+
+/// Controller buttons bound by default, the D-pad moves and A acts.
+const DEFAULT_BUTTONS: [(usize, &str); 5] = [
+    (0x2, "dpup"),
+    (0x4, "dpleft"),
+    (0x6, "dpright"),
+    (0x8, "dpdown"),
+    (0x5, "a"),
+];
+
+/// Built-in keyboard layouts, each places the CHIP-8 keypad on the same
+/// physical keys as `1234`, `QWER`, `ASDF` and `ZXCV` on a QWERTY keyboard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Qwertz,
+    Azerty,
+    Dvorak,
+    Colemak,
+}
+
+impl Layout {
+    /// Host keys bound to each CHIP-8 key, indexed by the CHIP-8 key.
+    pub(crate) fn keys(&self) -> [&'static [&'static str]; 16] {
+        match self {
+            Layout::Qwerty => [
+                &["X"],
+                &["1"],
+                &["2"],
+                &["3"],
+                &["Q"],
+                &["W"],
+                &["E"],
+                &["A"],
+                &["S"],
+                &["D"],
+                &["Z"],
+                &["C"],
+                &["4"],
+                &["R"],
+                &["F"],
+                &["V"],
+            ],
+            Layout::Qwertz => [
+                &["X"],
+                &["1"],
+                &["2"],
+                &["3"],
+                &["Q"],
+                &["W"],
+                &["E"],
+                &["A"],
+                &["S"],
+                &["D"],
+                &["Y"],
+                &["C"],
+                &["4"],
+                &["R"],
+                &["F"],
+                &["V"],
+            ],
+            // the number row needs shift on AZERTY, accept the plain keys too
+            Layout::Azerty => [
+                &["X"],
+                &["1", "&"],
+                &["2", "é"],
+                &["3", "\""],
+                &["A"],
+                &["Z"],
+                &["E"],
+                &["Q"],
+                &["S"],
+                &["D"],
+                &["W"],
+                &["C"],
+                &["4", "'"],
+                &["R"],
+                &["F"],
+                &["V"],
+            ],
+            Layout::Dvorak => [
+                &["Q"],
+                &["1"],
+                &["2"],
+                &["3"],
+                &["'"],
+                &[","],
+                &["."],
+                &["A"],
+                &["O"],
+                &["E"],
+                &[";"],
+                &["J"],
+                &["4"],
+                &["P"],
+                &["U"],
+                &["K"],
+            ],
+            Layout::Colemak => [
+                &["X"],
+                &["1"],
+                &["2"],
+                &["3"],
+                &["Q"],
+                &["W"],
+                &["F"],
+                &["A"],
+                &["R"],
+                &["S"],
+                &["Z"],
+                &["C"],
+                &["4"],
+                &["P"],
+                &["T"],
+                &["V"],
+            ],
+        }
+    }
+}
+
+/// A CHIP-8 key, written as a hex digit in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ChipKey(pub(crate) usize);
+
+impl TryFrom<String> for ChipKey {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<ChipKey, String> {
+        match usize::from_str_radix(&key, 16) {
+            Ok(index) if index < 16 => Ok(ChipKey(index)),
+            _ => Err(format!("Invalid CHIP-8 key {:?}, expected 0-F", key)),
+        }
+    }
+}
+
+/// Settings that can be given globally, per ROM or on the command line.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub layout: Option<Layout>,
+    pub keys: BTreeMap<ChipKey, Vec<String>>,
+    pub buttons: BTreeMap<ChipKey, Vec<String>>,
+    pub platform: Option<Platform>,
+    pub quirks: QuirkOverrides,
+    /// Instructions executed per frame.
+    pub tick_rate: Option<usize>,
+    pub theme: Option<Theme>,
+    pub palette: PaletteOverrides,
+    pub beeper: BeeperSettings,
+    /// Whether to open an audio device, on by default.
+    pub audio: Option<bool>,
+    pub persistence: Option<Persistence>,
+    /// Initial window size, as a multiple of the screen size.
+    pub scale: Option<u32>,
+    pub scaling: Option<Scaling>,
+    pub screenshot_dir: Option<PathBuf>,
+    /// Screenshot size, as a multiple of the screen size.
+    pub screenshot_scale: Option<usize>,
+    pub recording_dir: Option<PathBuf>,
+    /// Recording size, as a multiple of the screen size.
+    pub recording_scale: Option<usize>,
+    pub glyphs: Option<Glyphs>,
+    pub bell: Option<Bell>,
+}
+
+impl Settings {
+    /// Layers `other` on top of `self`, values set in `other` win.
+    pub(crate) fn merge(&self, other: &Settings) -> Settings {
+        let mut keys = self.keys.clone();
+        keys.extend(other.keys.clone());
+        let mut buttons = self.buttons.clone();
+        buttons.extend(other.buttons.clone());
+        Settings {
+            layout: other.layout.or(self.layout),
+            keys,
+            buttons,
+            platform: other.platform.or(self.platform),
+            quirks: self.quirks.merge(&other.quirks),
+            tick_rate: other.tick_rate.or(self.tick_rate),
+            theme: other.theme.or(self.theme),
+            palette: self.palette.merge(&other.palette),
+            beeper: self.beeper.merge(&other.beeper),
+            audio: other.audio.or(self.audio),
+            persistence: other.persistence.or(self.persistence),
+            scale: other.scale.or(self.scale),
+            scaling: other.scaling.or(self.scaling),
+            screenshot_dir: other.screenshot_dir.clone().or(self.screenshot_dir.clone()),
+            screenshot_scale: other.screenshot_scale.or(self.screenshot_scale),
+            recording_dir: other.recording_dir.clone().or(self.recording_dir.clone()),
+            recording_scale: other.recording_scale.or(self.recording_scale),
+            glyphs: other.glyphs.or(self.glyphs),
+            bell: other.bell.or(self.bell),
+        }
+    }
+
+    pub(crate) fn quirks(&self) -> Quirks {
+        let quirks = self
+            .platform
+            .map(|platform| platform.quirks())
+            .unwrap_or_default();
+        self.quirks.apply(quirks)
+    }
+
+    pub(crate) fn tick_rate(&self) -> usize {
+        self.tick_rate.unwrap_or(DEFAULT_TICK_RATE)
+    }
+
+    pub(crate) fn scale(&self) -> u32 {
+        self.scale.unwrap_or(DEFAULT_SCALE).max(1)
+    }
+
+    pub(crate) fn scaling(&self) -> Scaling {
+        self.scaling.unwrap_or_default()
+    }
+
+    pub(crate) fn screenshot_dir(&self) -> PathBuf {
+        self.screenshot_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SCREENSHOT_DIR))
+    }
+
+    pub(crate) fn screenshot_scale(&self) -> usize {
+        self.screenshot_scale.unwrap_or(1).max(1)
+    }
+
+    pub(crate) fn recording_dir(&self) -> PathBuf {
+        self.recording_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_RECORDING_DIR))
+    }
+
+    pub(crate) fn recording_scale(&self) -> usize {
+        self.recording_scale.unwrap_or(1).max(1)
+    }
+
+    pub(crate) fn audio(&self) -> bool {
+        self.audio.unwrap_or(true)
+    }
+
+    pub(crate) fn persistence(&self) -> Persistence {
+        self.persistence.unwrap_or_default()
+    }
+
+    pub(crate) fn palette(&self) -> Palette {
+        self.palette.apply(self.theme.unwrap_or_default().palette())
+    }
+
+    /// Host key names bound to each CHIP-8 key.
+    pub(crate) fn key_bindings(&self) -> [Vec<String>; 16] {
+        let mut bindings = self
+            .layout
+            .unwrap_or_default()
+            .keys()
+            .map(|names| names.iter().map(|name| name.to_string()).collect());
+        for (key, names) in &self.keys {
+            bindings[key.0] = names.clone();
+        }
+        bindings
+    }
+
+    /// Controller button names bound to each CHIP-8 key.
+    pub(crate) fn button_bindings(&self) -> [Vec<String>; 16] {
+        let mut bindings: [Vec<String>; 16] = Default::default();
+        for (key_id, name) in DEFAULT_BUTTONS {
+            bindings[key_id].push(name.to_string());
+        }
+        for (key, names) in &self.buttons {
+            bindings[key.0] = names.clone();
+        }
+        bindings
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(flatten)]
+    pub settings: Settings,
+    /// Per-ROM settings, keyed by the ROM file name or SHA-1.
+    pub roms: HashMap<String, Settings>,
+    /// Directory of the CHIP-8 community database.
+    pub rom_db: Option<PathBuf>,
+    #[serde(skip)]
+    pub database: Option<RomDatabase>,
+    /// Settings from the command line, they take precedence over everything else.
+    #[serde(skip)]
+    pub overrides: Settings,
+}
+
+impl Config {
+    /// Loads the config from `path`, or from `chip8.toml` in the current
+    /// directory if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG).exists() => Path::new(DEFAULT_CONFIG),
+            None => return Ok(Config::default()),
+        };
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    /// Resolves the settings used outside of a ROM, such as in the ROM browser.
+    pub(crate) fn global_settings(&self) -> Settings {
+        self.settings.merge(&self.overrides)
+    }
+
+    /// Resolves the settings used for `rom`, from the lowest precedence to
+    /// the highest: the global settings, the ROM metadata, the per-ROM
+    /// settings and the command line.
+    pub(crate) fn settings_for(&self, rom: &Path, metadata: &Metadata) -> Settings {
+        let file_name = rom.file_name().map(|name| name.to_string_lossy());
+
+        [file_name.as_deref(), Some(metadata.sha1.as_str())]
+            .into_iter()
+            .flatten()
+            .filter_map(|key| self.roms.get(key))
+            .chain([&self.overrides])
+            .fold(
+                self.settings.merge(&metadata.settings),
+                |settings, other| settings.merge(other),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Rgb;
+
+    #[test]
+    fn parses_doc_example() {
+        let doc = include_str!("config.rs");
+        let start = doc.find("//! ```toml\n").unwrap();
+        let example: String = doc[start..]
+            .lines()
+            .skip(1)
+            .take_while(|line| *line != "//! ```")
+            .map(|line| format!("{}\n", line.trim_start_matches("//!").trim_start()))
+            .collect();
+
+        let config: Config = toml::from_str(&example).unwrap();
+        let settings = &config.settings;
+        assert_eq!(settings.layout, Some(Layout::Azerty));
+        assert_eq!(settings.platform, Some(Platform::OriginalChip8));
+        assert_eq!(settings.tick_rate, Some(15));
+        assert_eq!(settings.theme, Some(Theme::Amber));
+        assert_eq!(settings.quirks.shift, Some(true));
+        assert_eq!(settings.palette.foreground, Some(Rgb(0xff, 0xb0, 0x00)));
+        assert_eq!(settings.beeper.volume, Some(0.5));
+        assert_eq!(settings.audio, Some(true));
+        assert_eq!(settings.bell, Some(Bell::Flash));
+        assert!(config.rom_db.is_some());
+        assert!(config.roms.contains_key("Blitz [David Winter].ch8"));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(toml::from_str::<Config>("tick_rat = 15").is_err());
+        assert!(toml::from_str::<Config>("[buttons]\nplatform = \"originalChip8\"").is_err());
+        assert!(toml::from_str::<Config>("[quirks]\ntheme = \"amber\"").is_err());
+        assert!(toml::from_str::<Config>("[palette]\naudio = true").is_err());
+        assert!(toml::from_str::<Config>("[beeper]\nscale = 4").is_err());
+        assert!(toml::from_str::<Config>("[roms.x]\nrom_db = \"db\"").is_err());
+    }
+}
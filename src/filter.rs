@@ -0,0 +1,95 @@
+//! Software phosphor persistence, to hide the flicker of sprites being erased
+//! and redrawn with XOR every frame.
+
+use serde::Deserialize;
+
+use crate::chip8::graphic::{HEIGHT, WIDTH};
+
+// a lit pixel fades out over 4 frames
+const FADE_STEP: u8 = 64;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Persistence {
+    /// Pixels are shown as they are.
+    #[default]
+    Off,
+    /// Pixels turned off fade out over several frames.
+    Fade,
+    /// Pixels lit in either of the last two frames are shown.
+    Max,
+}
+
+/// Turns frames into shades, from 0 for the background to 255 for the
+/// foreground.
+pub(crate) struct Filter {
+    persistence: Persistence,
+    shades: [[u8; WIDTH]; HEIGHT],
+    previous: [[u8; WIDTH]; HEIGHT],
+}
+
+impl Filter {
+    pub fn new(persistence: Persistence) -> Filter {
+        Filter {
+            persistence,
+            shades: [[0; WIDTH]; HEIGHT],
+            previous: [[0; WIDTH]; HEIGHT],
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.persistence != Persistence::Off
+    }
+
+    /// Adds `pixels` as the next frame, and returns the shades to show.
+    pub fn apply(&mut self, pixels: &[[u8; WIDTH]; HEIGHT]) -> &[[u8; WIDTH]; HEIGHT] {
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let lit = pixel != 0;
+                let shade = &mut self.shades[y][x];
+                *shade = match self.persistence {
+                    Persistence::Off => (lit as u8) * 255,
+                    Persistence::Fade if lit => 255,
+                    Persistence::Fade => shade.saturating_sub(FADE_STEP),
+                    Persistence::Max => (lit || self.previous[y][x] != 0) as u8 * 255,
+                };
+            }
+        }
+        self.previous = *pixels;
+
+        &self.shades
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shade of pixel (1, 2) in each frame, lit in the first one only.
+    fn shades(persistence: Persistence) -> Vec<u8> {
+        let mut filter = Filter::new(persistence);
+        let mut lit = [[0; WIDTH]; HEIGHT];
+        lit[2][1] = 1;
+        let blank = [[0; WIDTH]; HEIGHT];
+        [lit, blank, blank, blank, blank, blank]
+            .iter()
+            .map(|pixels| filter.apply(pixels)[2][1])
+            .collect()
+    }
+
+    #[test]
+    fn fades_pixels_out() {
+        assert_eq!(shades(Persistence::Fade), [255, 191, 127, 63, 0, 0]);
+    }
+
+    #[test]
+    fn holds_pixels_for_a_frame() {
+        assert_eq!(shades(Persistence::Max), [255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn shows_pixels_as_they_are() {
+        assert_eq!(shades(Persistence::Off), [255, 0, 0, 0, 0, 0]);
+        assert!(!Filter::new(Persistence::Off).enabled());
+    }
+}
@@ -1,5 +1,28 @@
+mod beeper;
 mod chip8;
+mod config;
+mod filter;
+mod message;
+mod metadata;
 mod opcode;
+mod palette;
+mod png;
+mod recorder;
+mod replay;
 mod ui;
 
-pub use chip8::VM as Chip8;
+pub use beeper::{BeeperSettings, Waveform};
+pub use chip8::{
+    buzzer::Buzzer,
+    graphic::{HEIGHT as SCREEN_HEIGHT, WIDTH as SCREEN_WIDTH},
+    quirks::{Platform, QuirkOverrides, Quirks},
+    Backend, Chip8Builder, Observer, VM as Chip8,
+};
+pub use config::{ChipKey, Config, Layout, Settings};
+pub use filter::Persistence;
+pub use message::Message;
+pub use metadata::{Colors, Metadata, RomDatabase};
+pub use opcode::Opcode;
+pub use palette::{Palette, PaletteOverrides, Rgb, Theme};
+pub use replay::Replay;
+pub use ui::{Bell, Browser, Glyphs, Scaling};
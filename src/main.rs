@@ -1,17 +1,199 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
-use chip8::Chip8;
-use clap::Parser;
+use anyhow::{bail, Result};
+use chip8::{
+    BeeperSettings, Bell, Browser, Chip8, Config, Glyphs, Layout, Message, PaletteOverrides,
+    Persistence, Platform, Replay, Rgb, RomDatabase, Scaling, Settings, Theme, Waveform,
+};
+use clap::{ArgGroup, Parser, ValueEnum};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Frontend {
+    /// A window, with sound and game controllers.
+    Sdl,
+    /// The terminal, to play over SSH.
+    Tui,
+}
 
 #[derive(Parser)]
+#[command(group(
+    ArgGroup::new("headless")
+        .multiple(true)
+        .args(["frames", "screenshot_at_frame", "replay"])
+))]
 struct Cli {
+    /// ROM file, or a directory of ROMs to pick from
     file: PathBuf,
+    /// Where the game is shown
+    #[arg(long, value_enum, default_value = "sdl")]
+    frontend: Frontend,
+    /// Characters the terminal frontend draws with
+    #[arg(long, value_enum)]
+    glyphs: Option<Glyphs>,
+    /// How the terminal frontend shows the buzzer
+    #[arg(long, value_enum)]
+    bell: Option<Bell>,
+    /// Config file, defaults to chip8.toml in the current directory
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Keyboard layout, takes precedence over the config file
+    #[arg(long, value_enum)]
+    layout: Option<Layout>,
+    /// Platform whose quirks are emulated
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+    /// Instructions executed per frame
+    #[arg(long)]
+    tick_rate: Option<usize>,
+    /// Color theme
+    #[arg(long, value_enum)]
+    theme: Option<Theme>,
+    /// Foreground color, as #rrggbb
+    #[arg(long)]
+    foreground: Option<Rgb>,
+    /// Background color, as #rrggbb
+    #[arg(long)]
+    background: Option<Rgb>,
+    /// Pitch of the buzzer, in Hz
+    #[arg(long)]
+    frequency: Option<f32>,
+    /// Waveform of the buzzer
+    #[arg(long, value_enum)]
+    waveform: Option<Waveform>,
+    /// Volume of the buzzer, from 0 to 1
+    #[arg(long)]
+    volume: Option<f32>,
+    /// Play without sound, and without opening an audio device
+    #[arg(long)]
+    no_audio: bool,
+    /// Phosphor persistence, to reduce flicker
+    #[arg(long, value_enum)]
+    persistence: Option<Persistence>,
+    /// Initial window size, as a multiple of the screen size
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: Option<u32>,
+    /// How the screen is scaled to the window
+    #[arg(long, value_enum)]
+    scaling: Option<Scaling>,
+    /// Directory screenshots are saved to
+    #[arg(long)]
+    screenshot_dir: Option<PathBuf>,
+    /// Screenshot size, as a multiple of the screen size
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    screenshot_scale: Option<u64>,
+    /// Run without a window for this many frames, then save a screenshot
+    #[arg(long, value_name = "N")]
+    screenshot_at_frame: Option<u64>,
+    /// Run without a window for this many frames
+    #[arg(long, value_name = "N", conflicts_with = "screenshot_at_frame")]
+    frames: Option<u64>,
+    /// Run without a window, pressing the keys listed in this file
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// Save the sound of a run without a window to this WAV file
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    wav: Option<PathBuf>,
+    /// Record from the start, to GIF, Y4M and WAV files
+    #[arg(long)]
+    record: bool,
+    /// Directory recordings are saved to
+    #[arg(long)]
+    recording_dir: Option<PathBuf>,
+    /// Recording size, as a multiple of the screen size
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    recording_scale: Option<u64>,
+    /// Directory of the CHIP-8 community database
+    #[arg(long)]
+    rom_db: Option<PathBuf>,
+}
+
+fn report(message: Message) {
+    match message {
+        Message::Info(_) => println!("{}", message),
+        Message::Warning(_) => eprintln!("{}", message),
+    }
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let mut chip8 = Chip8::load(args.file)?;
-    chip8.run()?;
+    let mut config = Config::load(args.config.as_deref())?;
+    config.overrides = Settings {
+        layout: args.layout,
+        platform: args.platform,
+        tick_rate: args.tick_rate,
+        theme: args.theme,
+        beeper: BeeperSettings {
+            frequency: args.frequency,
+            waveform: args.waveform,
+            volume: args.volume,
+        },
+        audio: args.no_audio.then_some(false),
+        persistence: args.persistence,
+        scale: args.scale,
+        scaling: args.scaling,
+        screenshot_dir: args.screenshot_dir,
+        screenshot_scale: args.screenshot_scale.map(|scale| scale as usize),
+        recording_dir: args.recording_dir,
+        recording_scale: args.recording_scale.map(|scale| scale as usize),
+        glyphs: args.glyphs,
+        bell: args.bell,
+        palette: PaletteOverrides {
+            foreground: args.foreground,
+            background: args.background,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    if let Some(rom_db) = args.rom_db.or(config.rom_db.clone()) {
+        config.database = Some(RomDatabase::load(rom_db)?);
+    }
+
+    if args.replay.is_some() || args.screenshot_at_frame.is_some() || args.frames.is_some() {
+        let replay = match &args.replay {
+            Some(path) => Replay::load(path)?,
+            None => Replay::default(),
+        };
+        let mut chip8 = Chip8::load(args.file, &config)?;
+        print!("{}", chip8.metadata());
+        if args.record {
+            chip8.start_recording()?;
+        }
+        if let Some(path) = &args.wav {
+            chip8.start_wav(path)?;
+        }
+        let frames = args
+            .screenshot_at_frame
+            .or(args.frames)
+            .unwrap_or(replay.frames());
+        let result = chip8.run_headless(frames, &replay);
+        // keep what was recorded up to an error too
+        if let Some(path) = chip8.stop_recording()? {
+            println!("Saved recording to {}", path.display());
+        }
+        if let Some(path) = chip8.stop_wav()? {
+            println!("Saved sound to {}", path.display());
+        }
+        result?;
+        if args.screenshot_at_frame.is_some() {
+            let path = chip8.save_screenshot()?;
+            println!("Saved screenshot to {}", path.display());
+        }
+    } else if args.file.is_dir() {
+        if args.frontend == Frontend::Tui {
+            bail!("The ROM browser needs the SDL frontend");
+        }
+        let mut browser = Browser::open(&args.file, &config)?;
+        browser.run(&config, report)?;
+    } else {
+        let mut chip8 = Chip8::load(args.file, &config)?;
+        print!("{}", chip8.metadata());
+        if args.record {
+            chip8.start_recording()?;
+        }
+        match args.frontend {
+            Frontend::Sdl => chip8.run(report)?,
+            Frontend::Tui => chip8.run_in_terminal(report)?,
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,18 @@
+use std::fmt;
+
+/// Something to tell the player while a game runs, such as where a
+/// screenshot was saved. The library never prints, the caller reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Info(String),
+    /// Something failed, the game runs on.
+    Warning(String),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Info(text) | Message::Warning(text) => f.write_str(text),
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! ROM metadata, from the CHIP-8 community database and from the `.txt`
+//! description shipped next to a ROM.
+//!
+//! The database is the `database` directory of
+//! <https://github.com/chip-8/chip-8-database>, only `programs.json` and
+//! `sha1-hashes.json` are read from it.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    chip8::quirks::{Platform, QuirkOverrides},
+    config::{ChipKey, Settings},
+    palette::{PaletteOverrides, Rgb},
+};
+
+#[derive(Debug, Deserialize)]
+struct Program {
+    title: String,
+    description: Option<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<usize>,
+    #[serde(default)]
+    keys: BTreeMap<String, usize>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, DatabaseQuirks>,
+}
+
+/// Quirks of a ROM in the database. Unlike the config, quirks we don't know
+/// are ignored, so a database update adding one still loads.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct DatabaseQuirks {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl From<DatabaseQuirks> for QuirkOverrides {
+    fn from(quirks: DatabaseQuirks) -> QuirkOverrides {
+        QuirkOverrides {
+            shift: quirks.shift,
+            memory_increment_by_x: quirks.memory_increment_by_x,
+            memory_leave_i_unchanged: quirks.memory_leave_i_unchanged,
+            wrap: quirks.wrap,
+            jump: quirks.jump,
+            vblank: quirks.vblank,
+            logic: quirks.logic,
+        }
+    }
+}
+
+/// Colors of a ROM, as `#rrggbb` strings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Colors {
+    /// Background first, then the colors of each plane.
+    #[serde(default)]
+    pub pixels: Vec<String>,
+    pub buzzer: Option<String>,
+    pub silence: Option<String>,
+}
+
+/// ROM index keyed by the SHA-1 of the ROM content.
+#[derive(Debug)]
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+}
+
+impl RomDatabase {
+    /// Loads the database from the directory containing `programs.json`
+    /// and `sha1-hashes.json`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<RomDatabase> {
+        let read = |name: &str| {
+            let path = dir.as_ref().join(name);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read ROM database {}", path.display()))
+        };
+
+        let programs = serde_json::from_str(&read("programs.json")?)
+            .context("Invalid ROM database programs.json")?;
+        let hashes = serde_json::from_str(&read("sha1-hashes.json")?)
+            .context("Invalid ROM database sha1-hashes.json")?;
+
+        Ok(RomDatabase { programs, hashes })
+    }
+
+    fn lookup(&self, sha1: &str) -> Option<(&Program, &Rom)> {
+        let program = self.programs.get(*self.hashes.get(sha1)?)?;
+        let rom = program.roms.get(sha1)?;
+        Some((program, rom))
+    }
+}
+
+/// Controller buttons for the key names used by the database.
+const KEY_HINT_BUTTONS: [(&str, &str); 6] = [
+    ("up", "dpup"),
+    ("down", "dpdown"),
+    ("left", "dpleft"),
+    ("right", "dpright"),
+    ("a", "a"),
+    ("b", "b"),
+];
+
+/// Everything known about a ROM.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub sha1: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// What each CHIP-8 key does, such as `("up", 5)`.
+    pub key_hints: Vec<(String, usize)>,
+    pub colors: Option<Colors>,
+    /// Settings preferred by the ROM.
+    pub settings: Settings,
+}
+
+impl Metadata {
+    /// Looks up the ROM at `rom`, whose content is `content`.
+    pub fn lookup(rom: &Path, content: &[u8], database: Option<&RomDatabase>) -> Metadata {
+        let mut metadata = Metadata {
+            sha1: format!("{:x}", Sha1::digest(content)),
+            ..Default::default()
+        };
+
+        if let Some((program, entry)) = database.and_then(|db| db.lookup(&metadata.sha1)) {
+            metadata.title = Some(program.title.clone());
+            metadata.description = program.description.clone();
+            metadata.key_hints = entry
+                .keys
+                .iter()
+                .map(|(name, key_id)| (name.clone(), *key_id))
+                .collect();
+            metadata.colors = entry.colors.clone();
+
+            // the first platform we know about is the preferred one
+            let platform = entry
+                .platforms
+                .iter()
+                .find_map(|id| Platform::from_id(id).map(|platform| (id, platform)));
+            if let Some((id, platform)) = platform {
+                metadata.settings.platform = Some(platform);
+                metadata.settings.quirks = entry
+                    .quirky_platforms
+                    .get(id)
+                    .copied()
+                    .map(QuirkOverrides::from)
+                    .unwrap_or_default();
+            }
+            metadata.settings.tick_rate = entry.tickrate;
+            if let Some(colors) = &entry.colors {
+                // skip the whole palette if a color is invalid, rather than shifting the others
+                let pixels = colors
+                    .pixels
+                    .iter()
+                    .map(|color| color.parse::<Rgb>())
+                    .collect::<Result<Vec<_>>>();
+                if let Ok(pixels) = pixels {
+                    metadata.settings.palette = PaletteOverrides::from_colors(&pixels);
+                }
+            }
+            metadata.settings.buttons = metadata.button_hints();
+        }
+
+        // the description next to the ROM is more complete than the database one
+        if let Ok(description) = std::fs::read(rom.with_extension("txt")) {
+            let description = String::from_utf8_lossy(&description).trim().to_string();
+            if !description.is_empty() {
+                metadata.description = Some(description);
+            }
+        }
+
+        metadata
+    }
+
+    /// Controller bindings following the key hints, they replace the default
+    /// bindings entirely.
+    fn button_hints(&self) -> BTreeMap<ChipKey, Vec<String>> {
+        if self.key_hints.is_empty() {
+            return BTreeMap::new();
+        }
+
+        let mut buttons: BTreeMap<ChipKey, Vec<String>> = (0..16)
+            .map(|key_id| (ChipKey(key_id), Vec::new()))
+            .collect();
+        for (name, key_id) in &self.key_hints {
+            let button = KEY_HINT_BUTTONS
+                .iter()
+                .find(|(hint, _)| hint == name)
+                .map(|(_, button)| button.to_string());
+            if let (Some(button), Some(names)) = (button, buttons.get_mut(&ChipKey(*key_id))) {
+                names.push(button);
+            }
+        }
+        buttons
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(title) = &self.title {
+            writeln!(f, "{}", title)?;
+        }
+        if let Some(description) = &self.description {
+            writeln!(f, "{}", description)?;
+        }
+        if !self.key_hints.is_empty() {
+            let hints = self
+                .key_hints
+                .iter()
+                .map(|(name, key_id)| format!("{}={:X}", name, key_id))
+                .collect::<Vec<_>>();
+            writeln!(f, "Keys: {}", hints.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const CONTENT: [u8; 4] = [0x60, 0x2A, 0x12, 0x02];
+
+    /// A database with one program whose ROM is `CONTENT`.
+    fn database(dir: &Path) -> RomDatabase {
+        let sha1 = format!("{:x}", Sha1::digest(CONTENT));
+        let programs = format!(
+            r##"[{{
+                "title": "Answer",
+                "description": "Sets V0 to 42.",
+                "authors": ["Someone"],
+                "roms": {{
+                    "{sha1}": {{
+                        "file": "answer.ch8",
+                        "platforms": ["unknownPlatform", "superchip", "originalChip8"],
+                        "tickrate": 20,
+                        "keys": {{ "up": 5, "a": 6 }},
+                        "colors": {{ "pixels": ["#000000", "#ff0000"] }},
+                        "quirkyPlatforms": {{
+                            "superchip": {{ "shift": false, "newQuirk": true }}
+                        }}
+                    }}
+                }}
+            }}]"##
+        );
+        let hashes = format!(r#"{{ "{sha1}": 0 }}"#);
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("programs.json"), programs).unwrap();
+        std::fs::write(dir.join("sha1-hashes.json"), hashes).unwrap();
+        let database = RomDatabase::load(dir);
+        std::fs::remove_dir_all(dir).unwrap();
+        database.unwrap()
+    }
+
+    #[test]
+    fn looks_up_roms_in_the_database() {
+        let dir = std::env::temp_dir().join(format!("chip8-database-{}", std::process::id()));
+        let database = database(&dir);
+        let rom = dir.join("answer.ch8");
+
+        let metadata = Metadata::lookup(&rom, &CONTENT, Some(&database));
+        assert_eq!(metadata.title.as_deref(), Some("Answer"));
+        assert_eq!(metadata.description.as_deref(), Some("Sets V0 to 42."));
+        assert_eq!(
+            metadata.key_hints,
+            [("a".to_string(), 6), ("up".to_string(), 5)]
+        );
+        let settings = &metadata.settings;
+        assert_eq!(settings.platform, Some(Platform::Superchip));
+        assert_eq!(settings.tick_rate, Some(20));
+        assert_eq!(settings.quirks.shift, Some(false));
+        assert_eq!(settings.quirks.jump, None);
+        assert_eq!(settings.palette.foreground, Some(Rgb(0xff, 0, 0)));
+        assert_eq!(settings.buttons[&ChipKey(5)], ["dpup"]);
+
+        // other ROMs are unknown
+        let metadata = Metadata::lookup(&rom, &[0x12, 0x00], Some(&database));
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.settings.platform, None);
+    }
+
+    #[test]
+    fn database_settings_sit_between_global_and_rom_settings() {
+        let dir = std::env::temp_dir().join(format!("chip8-precedence-{}", std::process::id()));
+        let mut config: Config = toml::from_str(
+            r#"
+            platform = "originalChip8"
+            tick_rate = 10
+            quirks = { jump = true, shift = true }
+
+            [roms."answer.ch8"]
+            tick_rate = 30
+            "#,
+        )
+        .unwrap();
+        config.database = Some(database(&dir));
+        let rom = dir.join("answer.ch8");
+
+        let metadata = Metadata::lookup(&rom, &CONTENT, config.database.as_ref());
+        let settings = config.settings_for(&rom, &metadata);
+        assert_eq!(settings.platform, Some(Platform::Superchip));
+        assert_eq!(settings.tick_rate, Some(30));
+        assert_eq!(settings.quirks.shift, Some(false));
+        assert_eq!(settings.quirks.jump, Some(true));
+    }
+}
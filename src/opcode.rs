@@ -1,75 +1,238 @@
+use std::fmt;
+
 use anyhow::{bail, Error};
 
-#[derive(Debug)]
-pub(crate) enum Opcode {
-    // 00E0
+/// A decoded instruction. Registers are indexed 0 to F, more instructions
+/// may be added in minor versions.
+///
+/// [`Opcode::decode`] and [`Opcode::encode`] convert from and to the
+/// instruction words, and `Display` prints Cowgod's mnemonics,
+/// `LD V1, 0x20`, or Octo's with `{:#}`, `v1 := 0x20`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Opcode {
+    /// 00E0
     ClearScreen,
-    // 00EE
+    /// 00EE
     Return,
-    // 1NNN
+    /// 1NNN
     Jump { addr: u16 },
-    // 2NNN
+    /// 2NNN
     CallSub { addr: u16 },
-    // 3XNN
+    /// 3XNN
     SkipIfEqualConst { x: usize, nn: u8 },
-    // 4XNN
+    /// 4XNN
     SkipIfNotEqualConst { x: usize, nn: u8 },
-    // 5XY0
+    /// 5XY0
     SkipIfEqual { x: usize, y: usize },
-    // 6XNN
+    /// 6XNN
     SetConst { x: usize, nn: u8 },
-    // 7XNN
+    /// 7XNN
     AddConst { x: usize, nn: u8 },
-    // 8XY0
+    /// 8XY0
     Assign { x: usize, y: usize },
-    // 8XY1
+    /// 8XY1
     AssignOr { x: usize, y: usize },
-    // 8XY2
+    /// 8XY2
     AssignAnd { x: usize, y: usize },
-    // 8XY3
+    /// 8XY3
     AssignXor { x: usize, y: usize },
-    // 8XY4
+    /// 8XY4
     AssignAdd { x: usize, y: usize },
-    // 8XY5
+    /// 8XY5
     AssignSub { x: usize, y: usize },
-    // 8XY6
-    AssignShift { x: usize },
-    // 8XY7
+    /// 8XY6
+    AssignShift { x: usize, y: usize },
+    /// 8XY7
     AssignRevSub { x: usize, y: usize },
-    // 8XYE
-    AssignRevShift { x: usize },
-    // 9XY0
+    /// 8XYE
+    AssignRevShift { x: usize, y: usize },
+    /// 9XY0
     SkipIfNotEqual { x: usize, y: usize },
-    // ANNN
+    /// ANNN
     RegAssign { addr: u16 },
-    // BXNN
-    JumpOffset { x: usize, addr: u16 },
-    // DXYN
+    /// BNNN, or BXNN with the jump quirk, X being the top nibble of `addr`
+    JumpOffset { addr: u16 },
+    /// CXNN
+    Random { x: usize, nn: u8 },
+    /// DXYN
     Draw { x: usize, y: usize, height: u8 },
-    // EX9E
+    /// EX9E
     SkipIfPress { x: usize },
-    // EXA1
+    /// EXA1
     SkipIfNotPress { x: usize },
-    // FX0A
+    /// FX0A
     AssignKey { x: usize },
-    // FX07
+    /// FX07
     AssignDelayTimer { x: usize },
-    // FX15
+    /// FX15
     DelayTimerAssign { x: usize },
-    // FX18
+    /// FX18
     SoundTimerAssign { x: usize },
-    // FX1E
+    /// FX1E
     RegAssignAdd { x: usize },
-    // FX29
+    /// FX29
     RegAssignFont { x: usize },
-    // FX33
+    /// FX33
     BinaryCodedDecimal { x: usize },
-    // FX55
+    /// FX55
     RegDump { x: usize },
-    // FX65
+    /// FX65
     RegLoad { x: usize },
 }
 
+impl Opcode {
+    /// Decodes an instruction word, failing on the ones no instruction uses,
+    /// 5XYN and 9XYN included when N isn't 0.
+    pub fn decode(raw: u16) -> Result<Opcode, Error> {
+        Opcode::try_from(raw)
+    }
+
+    /// The instruction word, the inverse of [`Opcode::decode`]. Fields that
+    /// don't fit, registers past F, addresses past FFF and sprite heights
+    /// past 15, panic in debug builds and are cut to their low bits
+    /// otherwise, so they never change the instruction.
+    pub fn encode(&self) -> u16 {
+        let nibble = |value: usize| {
+            debug_assert!(value < 0x10, "{:#X} does not fit in a nibble", value);
+            value as u16 & 0xF
+        };
+        let nnn = |addr: u16| {
+            debug_assert!(addr < 0x1000, "{:#X} is not a 12-bit address", addr);
+            addr & 0xFFF
+        };
+        let xy = |code: u16, x: usize, y: usize| code | nibble(x) << 8 | nibble(y) << 4;
+        let xnn = |code: u16, x: usize, nn: u8| code | nibble(x) << 8 | nn as u16;
+        match *self {
+            Opcode::ClearScreen => 0x00E0,
+            Opcode::Return => 0x00EE,
+            Opcode::Jump { addr } => 0x1000 | nnn(addr),
+            Opcode::CallSub { addr } => 0x2000 | nnn(addr),
+            Opcode::SkipIfEqualConst { x, nn } => xnn(0x3000, x, nn),
+            Opcode::SkipIfNotEqualConst { x, nn } => xnn(0x4000, x, nn),
+            Opcode::SkipIfEqual { x, y } => xy(0x5000, x, y),
+            Opcode::SetConst { x, nn } => xnn(0x6000, x, nn),
+            Opcode::AddConst { x, nn } => xnn(0x7000, x, nn),
+            Opcode::Assign { x, y } => xy(0x8000, x, y),
+            Opcode::AssignOr { x, y } => xy(0x8001, x, y),
+            Opcode::AssignAnd { x, y } => xy(0x8002, x, y),
+            Opcode::AssignXor { x, y } => xy(0x8003, x, y),
+            Opcode::AssignAdd { x, y } => xy(0x8004, x, y),
+            Opcode::AssignSub { x, y } => xy(0x8005, x, y),
+            Opcode::AssignShift { x, y } => xy(0x8006, x, y),
+            Opcode::AssignRevSub { x, y } => xy(0x8007, x, y),
+            Opcode::AssignRevShift { x, y } => xy(0x800E, x, y),
+            Opcode::SkipIfNotEqual { x, y } => xy(0x9000, x, y),
+            Opcode::RegAssign { addr } => 0xA000 | nnn(addr),
+            Opcode::JumpOffset { addr } => 0xB000 | nnn(addr),
+            Opcode::Random { x, nn } => xnn(0xC000, x, nn),
+            Opcode::Draw { x, y, height } => xy(0xD000, x, y) | nibble(height as usize),
+            Opcode::SkipIfPress { x } => xnn(0xE000, x, 0x9E),
+            Opcode::SkipIfNotPress { x } => xnn(0xE000, x, 0xA1),
+            Opcode::AssignDelayTimer { x } => xnn(0xF000, x, 0x07),
+            Opcode::AssignKey { x } => xnn(0xF000, x, 0x0A),
+            Opcode::DelayTimerAssign { x } => xnn(0xF000, x, 0x15),
+            Opcode::SoundTimerAssign { x } => xnn(0xF000, x, 0x18),
+            Opcode::RegAssignAdd { x } => xnn(0xF000, x, 0x1E),
+            Opcode::RegAssignFont { x } => xnn(0xF000, x, 0x29),
+            Opcode::BinaryCodedDecimal { x } => xnn(0xF000, x, 0x33),
+            Opcode::RegDump { x } => xnn(0xF000, x, 0x55),
+            Opcode::RegLoad { x } => xnn(0xF000, x, 0x65),
+        }
+    }
+
+    fn fmt_cowgod(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Opcode::ClearScreen => write!(f, "CLS"),
+            Opcode::Return => write!(f, "RET"),
+            Opcode::Jump { addr } => write!(f, "JP {:#05X}", addr),
+            Opcode::CallSub { addr } => write!(f, "CALL {:#05X}", addr),
+            Opcode::SkipIfEqualConst { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Opcode::SkipIfNotEqualConst { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Opcode::SkipIfEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Opcode::SetConst { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Opcode::AddConst { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Opcode::Assign { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Opcode::AssignOr { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Opcode::AssignAnd { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Opcode::AssignXor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Opcode::AssignAdd { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Opcode::AssignSub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Opcode::AssignShift { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Opcode::AssignRevSub { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Opcode::AssignRevShift { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Opcode::SkipIfNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Opcode::RegAssign { addr } => write!(f, "LD I, {:#05X}", addr),
+            Opcode::JumpOffset { addr } => write!(f, "JP V0, {:#05X}", addr),
+            Opcode::Random { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Opcode::Draw { x, y, height } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, height),
+            Opcode::SkipIfPress { x } => write!(f, "SKP V{:X}", x),
+            Opcode::SkipIfNotPress { x } => write!(f, "SKNP V{:X}", x),
+            Opcode::AssignDelayTimer { x } => write!(f, "LD V{:X}, DT", x),
+            Opcode::AssignKey { x } => write!(f, "LD V{:X}, K", x),
+            Opcode::DelayTimerAssign { x } => write!(f, "LD DT, V{:X}", x),
+            Opcode::SoundTimerAssign { x } => write!(f, "LD ST, V{:X}", x),
+            Opcode::RegAssignAdd { x } => write!(f, "ADD I, V{:X}", x),
+            Opcode::RegAssignFont { x } => write!(f, "LD F, V{:X}", x),
+            Opcode::BinaryCodedDecimal { x } => write!(f, "LD B, V{:X}", x),
+            Opcode::RegDump { x } => write!(f, "LD [I], V{:X}", x),
+            Opcode::RegLoad { x } => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+
+    fn fmt_octo(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // skips become conditions on the next instruction, the other way round
+        match *self {
+            Opcode::ClearScreen => write!(f, "clear"),
+            Opcode::Return => write!(f, "return"),
+            Opcode::Jump { addr } => write!(f, "jump {:#05x}", addr),
+            Opcode::CallSub { addr } => write!(f, ":call {:#05x}", addr),
+            Opcode::SkipIfEqualConst { x, nn } => write!(f, "if v{:x} != {:#04x} then", x, nn),
+            Opcode::SkipIfNotEqualConst { x, nn } => {
+                write!(f, "if v{:x} == {:#04x} then", x, nn)
+            }
+            Opcode::SkipIfEqual { x, y } => write!(f, "if v{:x} != v{:x} then", x, y),
+            Opcode::SetConst { x, nn } => write!(f, "v{:x} := {:#04x}", x, nn),
+            Opcode::AddConst { x, nn } => write!(f, "v{:x} += {:#04x}", x, nn),
+            Opcode::Assign { x, y } => write!(f, "v{:x} := v{:x}", x, y),
+            Opcode::AssignOr { x, y } => write!(f, "v{:x} |= v{:x}", x, y),
+            Opcode::AssignAnd { x, y } => write!(f, "v{:x} &= v{:x}", x, y),
+            Opcode::AssignXor { x, y } => write!(f, "v{:x} ^= v{:x}", x, y),
+            Opcode::AssignAdd { x, y } => write!(f, "v{:x} += v{:x}", x, y),
+            Opcode::AssignSub { x, y } => write!(f, "v{:x} -= v{:x}", x, y),
+            Opcode::AssignShift { x, y } => write!(f, "v{:x} >>= v{:x}", x, y),
+            Opcode::AssignRevSub { x, y } => write!(f, "v{:x} =- v{:x}", x, y),
+            Opcode::AssignRevShift { x, y } => write!(f, "v{:x} <<= v{:x}", x, y),
+            Opcode::SkipIfNotEqual { x, y } => write!(f, "if v{:x} == v{:x} then", x, y),
+            Opcode::RegAssign { addr } => write!(f, "i := {:#05x}", addr),
+            Opcode::JumpOffset { addr } => write!(f, "jump0 {:#05x}", addr),
+            Opcode::Random { x, nn } => write!(f, "v{:x} := random {:#04x}", x, nn),
+            Opcode::Draw { x, y, height } => write!(f, "sprite v{:x} v{:x} {}", x, y, height),
+            Opcode::SkipIfPress { x } => write!(f, "if v{:x} -key then", x),
+            Opcode::SkipIfNotPress { x } => write!(f, "if v{:x} key then", x),
+            Opcode::AssignDelayTimer { x } => write!(f, "v{:x} := delay", x),
+            Opcode::AssignKey { x } => write!(f, "v{:x} := key", x),
+            Opcode::DelayTimerAssign { x } => write!(f, "delay := v{:x}", x),
+            Opcode::SoundTimerAssign { x } => write!(f, "buzzer := v{:x}", x),
+            Opcode::RegAssignAdd { x } => write!(f, "i += v{:x}", x),
+            Opcode::RegAssignFont { x } => write!(f, "i := hex v{:x}", x),
+            Opcode::BinaryCodedDecimal { x } => write!(f, "bcd v{:x}", x),
+            Opcode::RegDump { x } => write!(f, "save v{:x}", x),
+            Opcode::RegLoad { x } => write!(f, "load v{:x}", x),
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_octo(f)
+        } else {
+            self.fmt_cowgod(f)
+        }
+    }
+}
+
 impl TryFrom<u16> for Opcode {
     type Error = Error;
 
@@ -93,7 +256,7 @@ impl TryFrom<u16> for Opcode {
             0x2000 => Ok(Opcode::CallSub { addr: nnn }),
             0x3000 => Ok(Opcode::SkipIfEqualConst { x, nn }),
             0x4000 => Ok(Opcode::SkipIfNotEqualConst { x, nn }),
-            0x5000 => Ok(Opcode::SkipIfEqual { x, y }),
+            0x5000 if n == 0 => Ok(Opcode::SkipIfEqual { x, y }),
             0x6000 => Ok(Opcode::SetConst { x, nn }),
             0x7000 => Ok(Opcode::AddConst { x, nn }),
             0x8000 => match raw & 0x000F {
@@ -103,14 +266,15 @@ impl TryFrom<u16> for Opcode {
                 0x0003 => Ok(Opcode::AssignXor { x, y }),
                 0x0004 => Ok(Opcode::AssignAdd { x, y }),
                 0x0005 => Ok(Opcode::AssignSub { x, y }),
-                0x0006 => Ok(Opcode::AssignShift { x }),
+                0x0006 => Ok(Opcode::AssignShift { x, y }),
                 0x0007 => Ok(Opcode::AssignRevSub { x, y }),
-                0x000E => Ok(Opcode::AssignRevShift { x }),
+                0x000E => Ok(Opcode::AssignRevShift { x, y }),
                 _ => invalid_opcode(),
             },
-            0x9000 => Ok(Opcode::SkipIfNotEqual { x, y }),
+            0x9000 if n == 0 => Ok(Opcode::SkipIfNotEqual { x, y }),
             0xA000 => Ok(Opcode::RegAssign { addr: nnn }),
-            0xB000 => Ok(Opcode::JumpOffset { x, addr: nnn }),
+            0xB000 => Ok(Opcode::JumpOffset { addr: nnn }),
+            0xC000 => Ok(Opcode::Random { x, nn }),
             0xD000 => Ok(Opcode::Draw { x, y, height: n }),
             0xE000 => match nn {
                 0x009E => Ok(Opcode::SkipIfPress { x }),
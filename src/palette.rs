@@ -0,0 +1,180 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Context, Error, Result};
+use serde::Deserialize;
+
+/// A color, written as `#rrggbb` or `#rgb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl FromStr for Rgb {
+    type Err = Error;
+
+    fn from_str(color: &str) -> Result<Rgb> {
+        let hex = color.strip_prefix('#').unwrap_or(color);
+        // also keeps the slicing below on character boundaries
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            bail!("Invalid color {:?}, expected #rrggbb", color);
+        }
+        let channels = match hex.len() {
+            3 => hex
+                .chars()
+                .map(|digit| u8::from_str_radix(&digit.to_string(), 16).map(|value| value * 0x11))
+                .collect::<Result<Vec<_>, _>>(),
+            6 => (0..6)
+                .step_by(2)
+                .map(|start| u8::from_str_radix(&hex[start..start + 2], 16))
+                .collect::<Result<Vec<_>, _>>(),
+            _ => bail!("Invalid color {:?}, expected #rrggbb", color),
+        }
+        .with_context(|| format!("Invalid color {:?}, expected #rrggbb", color))?;
+
+        Ok(Rgb(channels[0], channels[1], channels[2]))
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = Error;
+
+    fn try_from(color: String) -> Result<Rgb> {
+        color.parse()
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Colors indexed by the planes a pixel is lit in: the background, the
+/// foreground (first plane), the second XO-CHIP plane and both planes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Palette {
+    pub fn background(&self) -> Rgb {
+        self.0[0]
+    }
+
+    pub fn foreground(&self) -> Rgb {
+        self.0[1]
+    }
+
+    /// Blends the background and the foreground, `shade` goes from 0 for the
+    /// background to 255 for the foreground.
+    pub fn shade(&self, shade: u8) -> Rgb {
+        let (background, foreground) = (self.background(), self.foreground());
+        let mix = |background: u8, foreground: u8| {
+            let shade = shade as u16;
+            ((background as u16 * (255 - shade) + foreground as u16 * shade) / 255) as u8
+        };
+        Rgb(
+            mix(background.0, foreground.0),
+            mix(background.1, foreground.1),
+            mix(background.2, foreground.2),
+        )
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Theme::default().palette()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// White on black.
+    #[default]
+    Classic,
+    /// Amber monochrome monitor.
+    Amber,
+    /// Green phosphor monochrome monitor.
+    Green,
+    /// The default colors of the Octo IDE.
+    Octo,
+    /// Greenish LCD of early handhelds.
+    Lcd,
+}
+
+impl Theme {
+    pub fn palette(&self) -> Palette {
+        let colors = match self {
+            Theme::Classic => [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+            Theme::Amber => [0x1A0F00, 0xFFB000, 0xB36B00, 0xFFE0A0],
+            Theme::Green => [0x0A140A, 0x33FF66, 0x1F9940, 0xB3FFC6],
+            Theme::Octo => [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+            Theme::Lcd => [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F],
+        };
+        Palette(colors.map(|color| Rgb((color >> 16) as u8, (color >> 8) as u8, color as u8)))
+    }
+}
+
+/// Colors set individually, on top of the ones from the theme. The Octo
+/// names `fill` and `fill2` are accepted too.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaletteOverrides {
+    pub background: Option<Rgb>,
+    #[serde(alias = "fill")]
+    pub foreground: Option<Rgb>,
+    #[serde(alias = "fill2")]
+    pub plane2: Option<Rgb>,
+    pub blend: Option<Rgb>,
+}
+
+impl PaletteOverrides {
+    /// Reads the colors from a list ordered as in a `Palette`.
+    pub(crate) fn from_colors(colors: &[Rgb]) -> PaletteOverrides {
+        PaletteOverrides {
+            background: colors.first().copied(),
+            foreground: colors.get(1).copied(),
+            plane2: colors.get(2).copied(),
+            blend: colors.get(3).copied(),
+        }
+    }
+
+    /// Layers `other` on top of `self`, values set in `other` win.
+    pub(crate) fn merge(&self, other: &PaletteOverrides) -> PaletteOverrides {
+        PaletteOverrides {
+            background: other.background.or(self.background),
+            foreground: other.foreground.or(self.foreground),
+            plane2: other.plane2.or(self.plane2),
+            blend: other.blend.or(self.blend),
+        }
+    }
+
+    pub(crate) fn apply(&self, palette: Palette) -> Palette {
+        let [background, foreground, plane2, blend] = palette.0;
+        Palette([
+            self.background.unwrap_or(background),
+            self.foreground.unwrap_or(foreground),
+            self.plane2.unwrap_or(plane2),
+            self.blend.unwrap_or(blend),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!("#ffb000".parse::<Rgb>().unwrap(), Rgb(0xff, 0xb0, 0x00));
+        assert_eq!("0A0B0C".parse::<Rgb>().unwrap(), Rgb(0x0a, 0x0b, 0x0c));
+        assert_eq!("#fb0".parse::<Rgb>().unwrap(), Rgb(0xff, 0xbb, 0x00));
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for color in [
+            "", "#", "#ffb00", "#ffb0000", "#ffb00g", "#+f+f+f", "#aé€", "#éé",
+        ] {
+            assert!(color.parse::<Rgb>().is_err(), "{:?}", color);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Minimal PNG encoder for indexed images. CHIP-8 screens use a handful of
+//! colors, so packed pixels stored without compression stay small.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+
+use crate::palette::Rgb;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// largest block of uncompressed data a deflate stream can hold
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// An image whose pixels are indices into `colors`.
+pub(crate) struct Image<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub colors: &'a [Rgb],
+    pub pixels: &'a [u8],
+}
+
+impl Image<'_> {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create directory {}", dir.display()))?;
+        }
+        fs::write(path, self.encode()).with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let bit_depth: u8 = match self.colors.len() {
+            0..=2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            _ => 8,
+        };
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // indexed color, default compression, filter and no interlacing
+        header.extend([bit_depth, 3, 0, 0, 0]);
+
+        let palette = self
+            .colors
+            .iter()
+            .flat_map(|color| [color.0, color.1, color.2])
+            .collect::<Vec<_>>();
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"PLTE", &palette);
+        write_chunk(&mut png, b"IDAT", &zlib(&self.scanlines(bit_depth)));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Rows of packed pixels, each starting with the "none" filter type.
+    fn scanlines(&self, bit_depth: u8) -> Vec<u8> {
+        let per_byte = 8 / bit_depth as usize;
+        let mut data = Vec::new();
+        for row in self.pixels.chunks(self.width) {
+            data.push(0);
+            for pixels in row.chunks(per_byte) {
+                let byte = pixels.iter().enumerate().fold(0, |byte, (index, pixel)| {
+                    byte | pixel << (8 - bit_depth as usize * (index + 1))
+                });
+                data.push(byte);
+            }
+        }
+        data
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of stored deflate blocks.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks = data.chunks(MAX_STORED_BLOCK).collect::<Vec<_>>();
+    let last = blocks.len().saturating_sub(1);
+    for (index, block) in blocks.iter().enumerate() {
+        stream.push((index == last) as u8);
+        stream.extend((block.len() as u16).to_le_bytes());
+        stream.extend((!(block.len() as u16)).to_le_bytes());
+        stream.extend(*block);
+    }
+    if blocks.is_empty() {
+        stream.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the chunks of `png` and gives the header, palette and
+    /// scanlines, inflating the stored blocks `zlib` writes.
+    fn decode(png: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (chunk, crc) = rest[4..].split_at(4 + length);
+            assert_eq!(crc32(chunk).to_be_bytes(), crc[..4]);
+            chunks.push((chunk[..4].to_vec(), chunk[4..].to_vec()));
+            rest = &crc[4..];
+        }
+        let kinds = chunks
+            .iter()
+            .map(|(kind, _)| kind.as_slice())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+
+        let stream = &chunks[2].1;
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        assert_eq!((stream[0] as u16 * 256 + stream[1] as u16) % 31, 0);
+        let mut data = Vec::new();
+        let mut block = &stream[2..];
+        loop {
+            let last = block[0];
+            let length = u16::from_le_bytes([block[1], block[2]]);
+            assert_eq!(!length, u16::from_le_bytes([block[3], block[4]]));
+            data.extend(&block[5..5 + length as usize]);
+            block = &block[5 + length as usize..];
+            if last == 1 {
+                break;
+            }
+            assert_eq!(last, 0);
+        }
+        assert_eq!(block, adler32(&data).to_be_bytes());
+        (chunks[0].1.clone(), chunks[1].1.clone(), data)
+    }
+
+    /// Encodes and decodes back an image of `colors` colors.
+    fn round_trip(width: usize, height: usize, colors: usize) {
+        let colors = (0..colors)
+            .map(|index| Rgb(index as u8, 0x80, 0xFF - index as u8))
+            .collect::<Vec<_>>();
+        let pixels = (0..width * height)
+            .map(|index| (index * 7 % colors.len()) as u8)
+            .collect::<Vec<_>>();
+        let image = Image {
+            width,
+            height,
+            colors: &colors,
+            pixels: &pixels,
+        };
+        let (header, palette, data) = decode(&image.encode());
+
+        let bit_depth = header[8] as usize;
+        assert_eq!(
+            header[..8],
+            [(width as u32).to_be_bytes(), (height as u32).to_be_bytes()].concat()
+        );
+        assert_eq!(header[9..], [3, 0, 0, 0]);
+        assert_eq!(palette.len(), colors.len() * 3);
+        assert_eq!(palette[3..6], [colors[1].0, colors[1].1, colors[1].2]);
+
+        let stride = 1 + (width * bit_depth).div_ceil(8);
+        assert_eq!(data.len(), stride * height);
+        let mask = 0xFF >> (8 - bit_depth);
+        for (row, scanline) in data.chunks(stride).enumerate() {
+            assert_eq!(scanline[0], 0);
+            for column in 0..width {
+                let bit = column * bit_depth;
+                let pixel = scanline[1 + bit / 8] >> (8 - bit_depth - bit % 8) & mask;
+                assert_eq!(pixel, pixels[row * width + column], "({}, {})", column, row);
+            }
+        }
+    }
+
+    #[test]
+    fn computes_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn computes_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(&[0xFF; 5552]), 0xF18F_9B8C);
+    }
+
+    #[test]
+    fn decodes_back_to_the_pixels() {
+        round_trip(64, 32, 2);
+        round_trip(13, 5, 3);
+        round_trip(7, 3, 16);
+        // more than one stored block
+        round_trip(300, 300, 17);
+    }
+}
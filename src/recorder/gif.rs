@@ -0,0 +1,286 @@
+//! Animated GIF writer, one image per change of the screen.
+
+use std::{collections::HashMap, fs::File, io::BufWriter, io::Write};
+
+use anyhow::{bail, Result};
+
+use crate::palette::Rgb;
+
+// delays are in hundredths of a second, browsers slow down anything shorter
+// than 2 of them
+const MIN_DELAY: u64 = 2;
+const FRAME_RATE: u64 = 60;
+const MAX_CODES: u16 = 4096;
+
+pub(super) struct Gif {
+    file: BufWriter<File>,
+    width: u16,
+    height: u16,
+    // the image on screen, written once the next one shows up
+    pending: Vec<u8>,
+    frames: u64,
+    // time written so far, in hundredths of a second
+    written: u64,
+}
+
+impl Gif {
+    pub fn create(
+        mut file: BufWriter<File>,
+        width: usize,
+        height: usize,
+        colors: &[Rgb; 2],
+    ) -> Result<Gif> {
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            bail!(
+                "Cannot record a {}x{} GIF, it is at most {}x{}",
+                width,
+                height,
+                u16::MAX,
+                u16::MAX
+            );
+        };
+        file.write_all(b"GIF89a")?;
+        file.write_all(&width.to_le_bytes())?;
+        file.write_all(&height.to_le_bytes())?;
+        // global color table of 2 colors, no background color or aspect ratio
+        file.write_all(&[0x80, 0, 0])?;
+        for color in colors {
+            file.write_all(&[color.0, color.1, color.2])?;
+        }
+        // loop forever
+        file.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(Gif {
+            file,
+            width,
+            height,
+            pending: Vec::new(),
+            frames: 0,
+            written: 0,
+        })
+    }
+
+    /// Adds the next frame, `pixels` are color indices.
+    pub fn frame(&mut self, pixels: &[u8]) -> Result<()> {
+        if self.pending != pixels {
+            if self.pending.is_empty() || self.delay() >= MIN_DELAY {
+                self.flush()?;
+            }
+            // images shown too briefly are dropped, the next one takes their time
+            self.pending = pixels.to_vec();
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        self.file.write_all(&[0x3B])?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Time since the last written image, in hundredths of a second.
+    fn delay(&self) -> u64 {
+        self.frames * 100 / FRAME_RATE - self.written
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let delay = self.delay();
+        self.written += delay;
+
+        // graphic control extension, then the image descriptor
+        self.file.write_all(&[0x21, 0xF9, 0x04, 0])?;
+        self.file
+            .write_all(&(delay.min(u16::MAX as u64) as u16).to_le_bytes())?;
+        self.file.write_all(&[0, 0, 0x2C, 0, 0, 0, 0])?;
+        self.file.write_all(&self.width.to_le_bytes())?;
+        self.file.write_all(&self.height.to_le_bytes())?;
+        self.file.write_all(&[0])?;
+
+        // the smallest code size allowed, enough for 2 colors
+        let min_code_size = 2;
+        self.file.write_all(&[min_code_size])?;
+        for block in lzw(&self.pending, min_code_size).chunks(255) {
+            self.file.write_all(&[block.len() as u8])?;
+            self.file.write_all(block)?;
+        }
+        self.file.write_all(&[0])?;
+        Ok(())
+    }
+}
+
+/// Packs variable sized codes, least significant bits first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let first_size = min_code_size as u32 + 1;
+
+    let mut writer = BitWriter::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = first_size;
+    writer.write(clear, size);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        writer.write(end, size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = codes.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, size);
+        if next == MAX_CODES {
+            writer.write(clear, size);
+            codes.clear();
+            next = end + 1;
+            size = first_size;
+        } else {
+            if next >= 1 << size {
+                size += 1;
+            }
+            codes.insert((prefix, pixel), next);
+            next += 1;
+        }
+        prefix = pixel as u16;
+    }
+    writer.write(prefix, size);
+    writer.write(end, size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes LZW codes the way GIF decoders do, growing the code size once
+    /// the table fills up its current size.
+    fn unlzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+        let mut position = 0;
+        let mut read = |size: u32| {
+            let mut code = 0;
+            for bit in 0..size {
+                let byte = data[position / 8];
+                code |= ((byte >> (position % 8)) as u16 & 1) << bit;
+                position += 1;
+            }
+            code
+        };
+
+        let mut pixels = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = min_code_size as u32 + 1;
+        let mut previous: Option<u16> = None;
+        loop {
+            let code = read(size);
+            if code == clear {
+                table = (0..clear).map(|pixel| vec![pixel as u8]).collect();
+                // the clear and end codes take two entries
+                table.extend([Vec::new(), Vec::new()]);
+                size = min_code_size as u32 + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return pixels;
+            }
+            let entry = match previous {
+                None => table[code as usize].clone(),
+                Some(previous) => {
+                    let mut entry = table[previous as usize].clone();
+                    let first = match table.get(code as usize) {
+                        Some(known) => known[0],
+                        None => {
+                            assert_eq!(code as usize, table.len(), "unknown code");
+                            entry[0]
+                        }
+                    };
+                    entry.push(first);
+                    if table.len() < MAX_CODES as usize {
+                        table.push(entry);
+                        if table.len() == 1 << size && size < 12 {
+                            size += 1;
+                        }
+                    }
+                    table[code as usize].clone()
+                }
+            };
+            pixels.extend(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn encodes_lzw_codes() {
+        // clear (4), 1 then end (5), 3 bits each, least significant first
+        assert_eq!(lzw(&[1], 2), [0b0100_1100, 0b1]);
+        assert!(unlzw(&lzw(&[], 2), 2).is_empty());
+    }
+
+    #[test]
+    fn decodes_back_to_the_pixels() {
+        let mut seed = 1u32;
+        let mut random = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        };
+        let blank = vec![0; 64 * 32];
+        let checkers = (0..128 * 64)
+            .map(|index| (index % 2) as u8)
+            .collect::<Vec<_>>();
+        // past 4096 codes, the table starts over
+        let noise = (0..100_000).map(|_| random() & 1).collect::<Vec<_>>();
+        let sparse = (0..20_000)
+            .map(|_| (random() < 20) as u8)
+            .collect::<Vec<_>>();
+        for pixels in [vec![1], blank, checkers, noise, sparse] {
+            assert_eq!(unlzw(&lzw(&pixels, 2), 2), pixels);
+        }
+    }
+
+    #[test]
+    fn rejects_sizes_past_16_bits() {
+        let path = std::env::temp_dir().join(format!("chip8-{}.gif", std::process::id()));
+        let file = BufWriter::new(File::create(&path).unwrap());
+        let colors = [Rgb(0, 0, 0), Rgb(0xFF, 0xFF, 0xFF)];
+        let result = Gif::create(file, 64 * 1025, 32 * 1025, &colors);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Records every emulated frame to an animated GIF, a Y4M video and a WAV
+//! file of the buzzer.
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    beeper::BeeperSettings,
+    chip8::{
+        buzzer::Buzzer,
+        graphic::{HEIGHT, WIDTH},
+    },
+    palette::Palette,
+};
+
+use self::{gif::Gif, y4m::Y4m};
+
+pub(crate) use self::wav::Wav;
+
+mod gif;
+mod wav;
+mod y4m;
+
+pub(crate) struct Recorder {
+    path: PathBuf,
+    scale: usize,
+    gif: Gif,
+    y4m: Y4m,
+    wav: Wav,
+}
+
+impl Recorder {
+    /// Starts recording to `path` with the `.gif`, `.y4m` and `.wav`
+    /// extensions, each pixel `scale` times larger.
+    pub fn create(
+        path: &Path,
+        palette: &Palette,
+        beeper: &BeeperSettings,
+        scale: usize,
+    ) -> Result<Recorder> {
+        // the extension is appended, the name may have dots of its own
+        let create = |extension: &str| {
+            let mut file = path.as_os_str().to_owned();
+            file.push(".");
+            file.push(extension);
+            create_file(Path::new(&file))
+        };
+
+        let (width, height) = (WIDTH * scale, HEIGHT * scale);
+        let colors = [palette.background(), palette.foreground()];
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            scale,
+            gif: Gif::create(create("gif")?, width, height, &colors)?,
+            y4m: Y4m::create(create("y4m")?, width, height, &colors)?,
+            wav: Wav::create(create("wav")?, beeper)?,
+        })
+    }
+
+    /// Adds one emulated frame.
+    pub fn frame(&mut self, pixels: &[[u8; WIDTH]; HEIGHT], buzzer: &Buzzer) -> Result<()> {
+        let scale = self.scale;
+        let pixels = pixels
+            .iter()
+            .flat_map(|row| std::iter::repeat_n(row, scale))
+            .flat_map(|row| {
+                row.iter()
+                    .flat_map(move |&pixel| std::iter::repeat_n(pixel, scale))
+            })
+            .collect::<Vec<_>>();
+
+        self.gif.frame(&pixels)?;
+        self.y4m.frame(&pixels)?;
+        self.wav.frame(buzzer)
+    }
+
+    /// Completes the files, and returns their path without extension.
+    pub fn finish(self) -> Result<PathBuf> {
+        self.gif.finish()?;
+        self.y4m.finish()?;
+        self.wav.finish()?;
+        Ok(self.path)
+    }
+}
+
+/// Creates the file at `path` for writing, and its directory if needed.
+pub(crate) fn create_file(path: &Path) -> Result<BufWriter<File>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Cannot create directory {}", dir.display()))?;
+    }
+    File::create(path)
+        .map(BufWriter::new)
+        .with_context(|| format!("Cannot create {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_dots_in_the_name() {
+        let dir = std::env::temp_dir().join(format!("chip8-recorder-{}", std::process::id()));
+        let path = dir.join("Addition Problems [Paul C. Moews]-000123");
+        let recorder =
+            Recorder::create(&path, &Palette::default(), &BeeperSettings::default(), 1).unwrap();
+        assert_eq!(recorder.finish().unwrap(), path);
+        let mut files = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            files,
+            [
+                "Addition Problems [Paul C. Moews]-000123.gif",
+                "Addition Problems [Paul C. Moews]-000123.wav",
+                "Addition Problems [Paul C. Moews]-000123.y4m",
+            ]
+        );
+    }
+}
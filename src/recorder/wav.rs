@@ -0,0 +1,69 @@
+//! 16-bit mono WAV writer for the buzzer, always at 44.1 kHz so the files
+//! of two runs can be compared sample by sample.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+use anyhow::Result;
+
+use crate::{
+    beeper::{Beeper, BeeperSettings},
+    chip8::buzzer::Buzzer,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+// the RIFF header and the format chunk come before the samples
+const HEADER_SIZE: u32 = 44;
+
+pub(crate) struct Wav {
+    file: BufWriter<File>,
+    beeper: Beeper,
+    samples: u32,
+}
+
+impl Wav {
+    pub fn create(mut file: BufWriter<File>, beeper: &BeeperSettings) -> Result<Wav> {
+        // the sizes are filled in by `finish`
+        file.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM, mono
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        // 2 bytes per sample, 16 bits
+        file.write_all(&2u16.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data\0\0\0\0")?;
+
+        Ok(Wav {
+            file,
+            beeper: Beeper::new(SAMPLE_RATE, beeper),
+            samples: 0,
+        })
+    }
+
+    /// Adds the sound of one frame.
+    pub fn frame(&mut self, buzzer: &Buzzer) -> Result<()> {
+        let samples = self.beeper.frame(buzzer);
+        for sample in samples {
+            self.file
+                .write_all(&((sample * i16::MAX as f32) as i16).to_le_bytes())?;
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let data_size = self.samples * 2;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.file.write_all(&data_size.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! Uncompressed YUV4MPEG2 video writer, readable by ffmpeg and most players.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use anyhow::Result;
+
+use crate::palette::Rgb;
+
+pub(super) struct Y4m {
+    file: BufWriter<File>,
+    // Y, Cb and Cr of each color
+    colors: Vec<[u8; 3]>,
+}
+
+impl Y4m {
+    pub fn create(
+        mut file: BufWriter<File>,
+        width: usize,
+        height: usize,
+        colors: &[Rgb],
+    ) -> Result<Y4m> {
+        // full chroma, so single pixels keep their color
+        writeln!(file, "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444", width, height)?;
+        Ok(Y4m {
+            file,
+            colors: colors.iter().map(|&color| ycbcr(color)).collect(),
+        })
+    }
+
+    /// Adds the next frame, `pixels` are color indices.
+    pub fn frame(&mut self, pixels: &[u8]) -> Result<()> {
+        self.file.write_all(b"FRAME\n")?;
+        for plane in 0..3 {
+            for &pixel in pixels {
+                self.file.write_all(&[self.colors[pixel as usize][plane]])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts to the limited range BT.601 colors video players expect.
+fn ycbcr(Rgb(r, g, b): Rgb) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+    let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+    let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+    [y, cb, cr].map(|value| value.round() as u8)
+}
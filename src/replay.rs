@@ -0,0 +1,82 @@
+//! Key presses to play back without a window.
+//!
+//! A replay file has one line per change of the pressed keys: the frame it
+//! happens on, then the pressed CHIP-8 keys as hex digits, or `-` for none.
+//! The run stops at the last line.
+//!
+//! ```text
+//! # start the game, then hold left for a second
+//! 60 5
+//! 65 -
+//! 120 4
+//! 180 -
+//! ```
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::chip8::keymap::Keymap;
+
+#[derive(Debug, Default)]
+pub struct Replay {
+    // frame and pressed keys, ordered by frame
+    changes: Vec<(u64, [bool; 16])>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read replay {}", path.display()))?;
+        content
+            .parse()
+            .with_context(|| format!("Invalid replay {}", path.display()))
+    }
+
+    /// Number of frames the replay lasts.
+    pub fn frames(&self) -> u64 {
+        self.changes.last().map_or(0, |(frame, _)| *frame)
+    }
+
+    /// Keys pressed during `frame`, counting from 0.
+    pub(crate) fn keymap(&self, frame: u64) -> Keymap {
+        let mut keymap = Keymap::default();
+        let index = self.changes.partition_point(|(start, _)| *start <= frame);
+        if let Some((_, keys)) = index.checked_sub(1).map(|index| &self.changes[index]) {
+            (0..16)
+                .filter(|key_id| keys[*key_id])
+                .for_each(|key_id| keymap.set(key_id));
+        }
+        keymap
+    }
+}
+
+impl std::str::FromStr for Replay {
+    type Err = anyhow::Error;
+
+    fn from_str(content: &str) -> Result<Replay> {
+        let mut changes: Vec<(u64, [bool; 16])> = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let line_error = || format!("Line {}: {:?}", number + 1, line);
+            let (frame, keys) = line.split_once(' ').with_context(line_error)?;
+            let frame: u64 = frame.parse().with_context(line_error)?;
+            let mut pressed = [false; 16];
+            for digit in keys.trim().chars().filter(|c| *c != '-') {
+                let key_id = digit.to_digit(16).with_context(line_error)?;
+                pressed[key_id as usize] = true;
+            }
+
+            if changes.last().is_some_and(|(last, _)| *last > frame) {
+                bail!("{}, frames must be in order", line_error());
+            }
+            changes.push((frame, pressed));
+        }
+        Ok(Replay { changes })
+    }
+}
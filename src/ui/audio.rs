@@ -1,95 +1,129 @@
-use std::time::{Duration, SystemTime};
-
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
 use sdl2::{
-    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    audio::{AudioQueue, AudioSpecDesired},
     Sdl,
 };
 
+use crate::{
+    beeper::{Beeper, BeeperSettings},
+    chip8::{buzzer::Buzzer, clock::Speed},
+};
+
+const SAMPLE_RATE: i32 = 44100;
+// frames of sound queued ahead, enough to cover the jitter of frame pacing
+const LATENCY_FRAMES: u32 = 2;
+// past this many queued frames the emulation runs faster than real time,
+// new frames are dropped rather than played late
+const MAX_QUEUED_FRAMES: u32 = 6;
+
 pub(crate) struct Audio {
-    device: AudioDevice<SquareWave>,
-    next_pause: Option<SystemTime>,
+    // none when there is no sound, games still run the same
+    device: Option<AudioQueue<f32>>,
+    beeper: Beeper,
+    muted: bool,
 }
 
 impl Audio {
-    pub(crate) fn new(sdl_context: &Sdl) -> Result<Audio> {
+    /// Opens the default audio device, or falls back to silence with a
+    /// warning when there is none, as in containers.
+    pub(crate) fn new(sdl_context: &Sdl) -> (Audio, Option<String>) {
+        match Audio::open(sdl_context) {
+            Ok(device) => {
+                let beeper = Beeper::new(device.spec().freq as u32, &BeeperSettings::default());
+                device.resume();
+                let audio = Audio {
+                    device: Some(device),
+                    beeper,
+                    muted: false,
+                };
+                (audio, None)
+            }
+            Err(err) => (
+                Audio::null(),
+                Some(format!("{}, playing without sound", err)),
+            ),
+        }
+    }
+
+    /// Plays nothing.
+    pub(crate) fn null() -> Audio {
+        Audio {
+            device: None,
+            beeper: Beeper::new(SAMPLE_RATE as u32, &BeeperSettings::default()),
+            muted: false,
+        }
+    }
+
+    fn open(sdl_context: &Sdl) -> Result<AudioQueue<f32>> {
         let audio_subsystem = sdl_context
             .audio()
-            .ok()
-            .context("Cannot open audio system")?;
+            .map_err(|err| anyhow!("Cannot open audio system: {}", err))?;
 
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(SAMPLE_RATE),
             channels: Some(1), // mono
             samples: None,     // default sample size
         };
-        let audio_device = audio_subsystem
-            .open_playback(None, &desired_spec, |spec| {
-                // initialize the audio callback
-                SquareWave {
-                    phase_inc: 440.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.25,
-                }
-            })
-            .expect("Cannot open audio device");
+        audio_subsystem
+            .open_queue::<f32, _>(None, &desired_spec)
+            .map_err(|err| anyhow!("Cannot open audio device: {}", err))
+    }
 
-        Ok(Self {
-            device: audio_device,
-            next_pause: None,
-        })
+    pub(crate) fn configure(&mut self, settings: &BeeperSettings) {
+        self.beeper.configure(settings);
     }
 
     pub(crate) fn run(&mut self, command: AudioCommand) {
         match command {
-            AudioCommand::Resume => self.resume(),
-            AudioCommand::TryPause => self.try_pause(),
+            AudioCommand::Pause => self.clear(),
+            AudioCommand::Mute => {
+                self.muted = true;
+                self.clear();
+            }
+            AudioCommand::Unmute => self.muted = false,
+            // frames last longer than their sound below 100%, it would run
+            // dry and be padded with silence
+            AudioCommand::Speed(speed) => self.beeper.set_stretch(speed.slowdown()),
         }
     }
 
-    fn resume(&mut self) {
-        self.device.resume();
-        self.next_pause = Some(SystemTime::now() + Duration::from_secs(1));
+    fn clear(&self) {
+        if let Some(device) = &self.device {
+            device.clear();
+        }
     }
 
-    fn try_pause(&mut self) {
-        if self.can_pause() {
-            self.device.pause();
-            self.next_pause = None;
+    /// Queues the sound of one emulated frame.
+    pub(crate) fn play(&mut self, buzzer: &Buzzer) -> Result<()> {
+        // samples still count while muted, so the buzzer stays in time
+        let samples = self.beeper.frame(buzzer);
+        let Some(device) = &self.device else {
+            return Ok(());
+        };
+        if self.muted {
+            return Ok(());
         }
-    }
 
-    fn can_pause(&self) -> bool {
-        match self.next_pause {
-            Some(next_stop) => SystemTime::now() >= next_stop,
-            None => false,
+        let frame_size = std::mem::size_of_val(samples);
+        let queued = device.size() as usize;
+        if queued == 0 {
+            // ran dry, queue some silence first so this doesn't happen every frame
+            let silence = vec![0.0; samples.len() * LATENCY_FRAMES as usize];
+            let _ = device.queue_audio(&silence);
+        } else if queued > frame_size * MAX_QUEUED_FRAMES as usize {
+            return Ok(());
         }
+        device
+            .queue_audio(samples)
+            .map_err(|err| anyhow!("Cannot queue audio: {}", err))
     }
 }
 
 pub(crate) enum AudioCommand {
-    Resume,
-    TryPause,
-}
-
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
-
-impl AudioCallback for SquareWave {
-    type Channel = f32;
-
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
-    }
+    /// Stops what is queued, when leaving a game.
+    Pause,
+    Mute,
+    Unmute,
+    /// The emulation speed, the sound is stretched to slower ones.
+    Speed(Speed),
 }
@@ -0,0 +1,193 @@
+//! Menu listing the ROMs of a directory, to pick a game without restarting
+//! the emulator.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    chip8::{Exit, VM},
+    config::Config,
+    message::Message,
+    metadata::Metadata,
+};
+
+use super::{
+    display::{Display, TEXT_COLUMNS, TEXT_ROWS},
+    input::MenuCommand,
+    Frontend, UI,
+};
+
+// the header takes the first row, a blank row separates the description
+const LIST_ROWS: usize = 11;
+const DESCRIPTION_ROWS: usize = TEXT_ROWS - LIST_ROWS - 2;
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    description: Vec<String>,
+}
+
+pub struct Browser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+}
+
+impl Browser {
+    /// Lists the `.ch8` files in `dir`.
+    pub fn open<P: AsRef<Path>>(dir: P, config: &Config) -> Result<Browser> {
+        let dir = dir.as_ref();
+        let mut paths = std::fs::read_dir(dir)
+            .with_context(|| format!("Cannot read directory {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("ch8"))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        if paths.is_empty() {
+            bail!("No .ch8 files in {}", dir.display());
+        }
+
+        let entries = paths
+            .into_iter()
+            .map(|path| {
+                let content = std::fs::read(&path)
+                    .with_context(|| format!("Cannot read {}", path.display()))?;
+                let metadata = Metadata::lookup(&path, &content, config.database.as_ref());
+                let name = metadata.title.clone().unwrap_or_else(|| {
+                    path.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                });
+                let description = wrap(
+                    metadata.description.as_deref().unwrap_or_default(),
+                    TEXT_COLUMNS,
+                );
+                Ok(Entry {
+                    path,
+                    name,
+                    description,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Browser {
+            dir: dir.to_path_buf(),
+            entries,
+            selected: 0,
+        })
+    }
+
+    /// Shows the menu and runs the picked games, `report` tells the player
+    /// what happened along the way.
+    pub fn run(&mut self, config: &Config, mut report: impl FnMut(Message)) -> Result<()> {
+        let mut ui = UI::new(&config.global_settings())?;
+        let mut rerender = true;
+
+        loop {
+            if rerender {
+                // games may have changed the palette
+                ui.display.set_palette(config.global_settings().palette());
+                ui.display.set_title("chip8")?;
+                self.render(&mut ui.display)?;
+                rerender = false;
+            }
+
+            let commands = ui.input.poll_menu();
+            for warning in ui.take_warnings() {
+                report(Message::Warning(warning));
+            }
+            for command in commands {
+                let last = self.entries.len() - 1;
+                match command {
+                    MenuCommand::Up => self.selected = self.selected.saturating_sub(1),
+                    MenuCommand::Down => self.selected = (self.selected + 1).min(last),
+                    MenuCommand::PageUp => self.selected = self.selected.saturating_sub(LIST_ROWS),
+                    MenuCommand::PageDown => self.selected = (self.selected + LIST_ROWS).min(last),
+                    MenuCommand::Select => {
+                        let entry = &self.entries[self.selected];
+                        match VM::load(&entry.path, config)
+                            .and_then(|mut vm| vm.play(&mut ui, &mut report))
+                        {
+                            Ok(Exit::Quit) => return Ok(()),
+                            Ok(Exit::Back) => {}
+                            // a broken ROM should not take the menu down with it
+                            Err(err) => report(Message::Warning(format!(
+                                "{}: {:#}",
+                                entry.path.display(),
+                                err
+                            ))),
+                        }
+                    }
+                    MenuCommand::ToggleFullscreen => ui.display.toggle_fullscreen()?,
+                    MenuCommand::Redraw => {}
+                    MenuCommand::Quit => return Ok(()),
+                }
+                rerender = true;
+            }
+
+            std::thread::sleep(Duration::from_secs(1) / 60);
+        }
+    }
+
+    fn render(&self, display: &mut Display) -> Result<()> {
+        let first = self
+            .selected
+            .saturating_sub(LIST_ROWS / 2)
+            .min(self.entries.len().saturating_sub(LIST_ROWS));
+
+        let header = format!(
+            "{} ({}/{})",
+            self.dir.display(),
+            self.selected + 1,
+            self.entries.len()
+        );
+        let mut lines = vec![(header, false)];
+        lines.extend(
+            self.entries
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(LIST_ROWS)
+                .map(|(index, entry)| (entry.name.clone(), index == self.selected)),
+        );
+        lines.resize(LIST_ROWS + 2, (String::new(), false));
+        lines.extend(
+            self.entries[self.selected]
+                .description
+                .iter()
+                .take(DESCRIPTION_ROWS)
+                .map(|line| (line.clone(), false)),
+        );
+
+        display.render_text(&lines)
+    }
+}
+
+/// Wraps `text` into lines of at most `width` characters, breaking at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        // keep blank lines, they separate paragraphs
+        lines.push(line);
+    }
+    lines
+}
@@ -1,54 +1,236 @@
 use anyhow::{Context, Result};
+use serde::Deserialize;
 
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, Sdl};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::{FullscreenType, Window},
+    Sdl,
+};
 
-use crate::chip8::graphic::{HEIGHT, WIDTH};
+use crate::{
+    chip8::graphic::{HEIGHT, WIDTH},
+    palette::{Palette, Rgb},
+};
 
-const SCALE: usize = 10;
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// text is laid out on a screen of this size, then scaled to the window
+const TEXT_SCREEN_WIDTH: usize = 640;
+const TEXT_SCREEN_HEIGHT: usize = 320;
+const TEXT_SCALE: usize = 2;
+// glyphs are separated by one blank pixel
+const CELL_WIDTH: usize = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+const CELL_HEIGHT: usize = (GLYPH_HEIGHT + 1) * TEXT_SCALE;
+pub(crate) const TEXT_COLUMNS: usize = TEXT_SCREEN_WIDTH / CELL_WIDTH;
+pub(crate) const TEXT_ROWS: usize = TEXT_SCREEN_HEIGHT / CELL_HEIGHT;
+
+/// How the screen is scaled to the window, the space left around it is
+/// filled with black bars.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    /// Scale by whole numbers only, so every pixel has the same size.
+    #[default]
+    Integer,
+    /// Fill as much of the window as possible, keeping the aspect ratio.
+    Fit,
+}
 
 pub(crate) struct Display {
     canvas: Canvas<Window>,
+    // the screen, recreated when its resolution changes
+    texture: Option<Texture>,
+    // RGB color of each shade, follows the palette
+    shade_colors: [[u8; 3]; 256],
+    palette: Palette,
+    scaling: Scaling,
+}
+
+fn color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.0, rgb.1, rgb.2)
 }
 
 impl Display {
-    pub(crate) fn new(sdl_context: &Sdl) -> Result<Display> {
+    /// Opens a window showing the screen `scale` times larger.
+    pub(crate) fn new(sdl_context: &Sdl, scale: u32) -> Result<Display> {
         let video_subsystem = sdl_context
             .video()
             .ok()
             .context("Cannot open video system")?;
 
         let window = video_subsystem
-            .window("chip8", (WIDTH * SCALE) as u32, (HEIGHT * SCALE) as u32)
+            .window("chip8", WIDTH as u32 * scale, HEIGHT as u32 * scale)
             .position_centered()
+            .resizable()
             .build()?;
 
-        Ok(Display {
-            canvas: window.into_canvas().present_vsync().build()?,
-        })
+        let mut display = Display {
+            // frames are paced by the emulated clock, not by the monitor
+            canvas: window.into_canvas().build()?,
+            texture: None,
+            shade_colors: [[0; 3]; 256],
+            palette: Palette::default(),
+            scaling: Scaling::default(),
+        };
+        display.set_palette(Palette::default());
+        Ok(display)
+    }
+
+    pub(crate) fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        for (shade, rgb) in self.shade_colors.iter_mut().enumerate() {
+            let Rgb(r, g, b) = palette.shade(shade as u8);
+            *rgb = [r, g, b];
+        }
+    }
+
+    pub(crate) fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
     }
 
-    pub(crate) fn render(&mut self, point_locations: &[(usize, usize)]) -> Result<()> {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    pub(crate) fn toggle_fullscreen(&mut self) -> Result<()> {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window
+            .set_fullscreen(fullscreen)
+            .ok()
+            .context("Cannot toggle fullscreen")?;
+        Ok(())
+    }
+
+    /// Maps a `width` x `height` screen to the window, and clears the window.
+    fn begin(&mut self, width: usize, height: usize, integer: bool) -> Result<()> {
+        self.canvas
+            .set_logical_size(width as u32, height as u32)
+            .context("Cannot scale screen")?;
+        self.canvas
+            .set_integer_scale(integer)
+            .ok()
+            .context("Cannot scale screen")?;
+
+        // the letterbox, then the screen itself
+        self.canvas.set_draw_color(Color::BLACK);
         self.canvas.clear();
+        self.canvas.set_draw_color(color(self.palette.background()));
+        self.canvas.fill_rect(None).ok().context("Cannot draw rect")
+    }
 
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-
-        let rects = point_locations
-            .iter()
-            .map(|(x, y)| {
-                Rect::new(
-                    (x * SCALE) as i32,
-                    (y * SCALE) as i32,
-                    SCALE as u32,
-                    SCALE as u32,
-                )
-            })
-            .collect::<Vec<_>>();
+    pub(crate) fn set_title(&mut self, title: &str) -> Result<()> {
+        self.canvas.window_mut().set_title(title)?;
+        Ok(())
+    }
+
+    /// Draws a screen of any resolution, `shades` go from 0 for the
+    /// background to 255 for the foreground.
+    pub(crate) fn render<const W: usize, const H: usize>(
+        &mut self,
+        shades: &[[u8; W]; H],
+    ) -> Result<()> {
+        let size = (W as u32, H as u32);
+        let texture_size = self.texture.as_ref().map(|texture| {
+            let query = texture.query();
+            (query.width, query.height)
+        });
+        if texture_size != Some(size) {
+            let texture = self
+                .canvas
+                .texture_creator()
+                .create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+                .context("Cannot create screen texture")?;
+            if let Some(previous) = self.texture.replace(texture) {
+                // SAFETY: the canvas it was created from is still alive
+                unsafe { previous.destroy() };
+            }
+        }
 
+        self.begin(W, H, self.scaling == Scaling::Integer)?;
+
+        let Some(texture) = &mut self.texture else {
+            unreachable!("the texture was created above");
+        };
+        let shade_colors = &self.shade_colors;
+        texture
+            .with_lock(None, |pixels, pitch| {
+                for (row, line) in shades.iter().zip(pixels.chunks_mut(pitch)) {
+                    for (&shade, pixel) in row.iter().zip(line.chunks_exact_mut(3)) {
+                        pixel.copy_from_slice(&shade_colors[shade as usize]);
+                    }
+                }
+            })
+            .ok()
+            .context("Cannot update screen texture")?;
         self.canvas
-            .fill_rects(&rects)
+            .copy(texture, None, None)
             .ok()
-            .context("Cannot draw rect")?;
+            .context("Cannot draw screen texture")?;
+
+        self.canvas.present();
+
+        Ok(())
+    }
+
+    /// Draws one line of text per row, highlighted lines are inverted.
+    pub(crate) fn render_text(&mut self, lines: &[(String, bool)]) -> Result<()> {
+        let background = color(self.palette.background());
+        let foreground = color(self.palette.foreground());
+        // text stays readable at any size, no need for whole numbers
+        self.begin(TEXT_SCREEN_WIDTH, TEXT_SCREEN_HEIGHT, false)?;
+
+        for (row, (text, highlighted)) in lines.iter().enumerate().take(TEXT_ROWS) {
+            let y = row * CELL_HEIGHT;
+            let text_color = if *highlighted {
+                self.canvas.set_draw_color(foreground);
+                self.canvas
+                    .fill_rect(Rect::new(
+                        0,
+                        y as i32,
+                        TEXT_SCREEN_WIDTH as u32,
+                        CELL_HEIGHT as u32,
+                    ))
+                    .ok()
+                    .context("Cannot draw rect")?;
+                background
+            } else {
+                foreground
+            };
+
+            let rects = text
+                .chars()
+                .take(TEXT_COLUMNS)
+                .enumerate()
+                .flat_map(|(column, c)| {
+                    let x = column * CELL_WIDTH;
+                    font::glyph(c)
+                        .iter()
+                        .enumerate()
+                        .flat_map(move |(glyph_y, glyph_row)| {
+                            (0..GLYPH_WIDTH)
+                                .filter(move |glyph_x| {
+                                    glyph_row >> (GLYPH_WIDTH - 1 - glyph_x) & 1 == 1
+                                })
+                                .map(move |glyph_x| {
+                                    Rect::new(
+                                        (x + glyph_x * TEXT_SCALE) as i32,
+                                        (y + glyph_y * TEXT_SCALE) as i32,
+                                        TEXT_SCALE as u32,
+                                        TEXT_SCALE as u32,
+                                    )
+                                })
+                        })
+                })
+                .collect::<Vec<_>>();
+
+            self.canvas.set_draw_color(text_color);
+            self.canvas
+                .fill_rects(&rects)
+                .ok()
+                .context("Cannot draw rect")?;
+        }
 
         self.canvas.present();
 
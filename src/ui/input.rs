@@ -1,15 +1,65 @@
+use std::collections::HashMap;
+
 use anyhow::{Context, Result};
-use sdl2::{event::Event, keyboard::Keycode, EventPump, Sdl};
+use sdl2::{
+    controller::{Button, GameController},
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    EventPump, GameControllerSubsystem, Sdl,
+};
 
-use crate::chip8::keymap::Keymap;
+use crate::{
+    chip8::{clock::Speed, keymap::Keymap},
+    config::Settings,
+};
 
 pub(crate) enum PollResult {
-    Stop,
-    Keymap(Keymap),
+    /// The window was closed.
+    Quit,
+    /// Escape was pressed.
+    Back,
+    Keymap {
+        keymap: Keymap,
+        hotkeys: Vec<Hotkey>,
+    },
+}
+
+pub(crate) enum Hotkey {
+    TogglePause,
+    AdvanceFrame,
+    Reset,
+    Reload,
+    Speed(Speed),
+    FastForward(bool),
+    ToggleFullscreen,
+    ToggleRecording,
+    ToggleMute,
+    Screenshot,
+    /// The window was resized or uncovered.
+    Redraw,
+}
+
+pub(crate) enum MenuCommand {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Select,
+    ToggleFullscreen,
+    /// The window was resized or uncovered.
+    Redraw,
+    Quit,
 }
 
 pub(crate) struct Input {
     event_pump: EventPump,
+    bindings: HashMap<Keycode, usize>,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // opened controllers, keyed by their instance id
+    controllers: HashMap<u32, GameController>,
+    button_bindings: Vec<(Button, usize)>,
+    // problems that leave input working, for the caller to report
+    warnings: Vec<String>,
 }
 
 impl Input {
@@ -19,15 +69,112 @@ impl Input {
             .ok()
             .context("Cannot open event pump")?;
 
-        Ok(Input { event_pump })
+        // playing without controllers is fine, keyboard input still works
+        let mut warnings = Vec::new();
+        let controller_subsystem = match sdl_context.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(err) => {
+                warnings.push(format!("Cannot open game controller system: {}", err));
+                None
+            }
+        };
+
+        Ok(Input {
+            event_pump,
+            bindings: HashMap::new(),
+            controller_subsystem,
+            controllers: HashMap::new(),
+            button_bindings: Vec::new(),
+            warnings,
+        })
+    }
+
+    /// Replaces the key and button bindings with the ones from `settings`.
+    pub(crate) fn bind(&mut self, settings: &Settings) -> Result<()> {
+        let mut bindings = HashMap::new();
+        for (key_id, names) in settings.key_bindings().iter().enumerate() {
+            for name in names {
+                let keycode = Keycode::from_name(name)
+                    .with_context(|| format!("Unknown key name {:?}", name))?;
+                if hotkey(&HashMap::new(), keycode, Mod::NOMOD, false).is_some() {
+                    self.warnings.push(format!(
+                        "{} is bound to CHIP-8 key {:X}, its hotkey is off",
+                        name, key_id
+                    ));
+                }
+                bindings.insert(keycode, key_id);
+            }
+        }
+
+        let mut button_bindings = Vec::new();
+        for (key_id, names) in settings.button_bindings().iter().enumerate() {
+            for name in names {
+                let button = Button::from_string(name)
+                    .with_context(|| format!("Unknown controller button {:?}", name))?;
+                button_bindings.push((button, key_id));
+            }
+        }
+
+        self.bindings = bindings;
+        self.button_bindings = button_bindings;
+        Ok(())
+    }
+
+    fn open_controller(&mut self, joystick_index: u32) {
+        let Some(subsystem) = &self.controller_subsystem else {
+            return;
+        };
+        match subsystem.open(joystick_index) {
+            Ok(controller) => {
+                self.controllers
+                    .insert(controller.instance_id(), controller);
+            }
+            Err(err) => self.warnings.push(format!(
+                "Cannot open game controller {}: {}",
+                joystick_index, err
+            )),
+        }
+    }
+
+    /// The warnings since the last call.
+    pub(crate) fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
     }
 
     pub(crate) fn poll(&mut self) -> PollResult {
+        let mut hotkeys = Vec::new();
+        let mut added_controllers = Vec::new();
         for event in self.event_pump.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return PollResult::Stop;
+            match event {
+                Event::Quit { .. } => return PollResult::Quit,
+                // also sent for controllers connected before startup
+                Event::ControllerDeviceAdded { which, .. } => added_controllers.push(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers.remove(&which);
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => hotkeys.push(Hotkey::Redraw),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    ..
+                } => hotkeys.extend(hotkey(&self.bindings, keycode, keymod, repeat)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } if !self.bindings.contains_key(&Keycode::Tab) => {
+                    hotkeys.push(Hotkey::FastForward(false))
+                }
+                _ => {}
             }
         }
+        for joystick_index in added_controllers {
+            self.open_controller(joystick_index);
+        }
+
         let keys = self
             .event_pump
             .keyboard_state()
@@ -36,33 +183,154 @@ impl Input {
             .collect::<Vec<Keycode>>();
 
         if keys.iter().any(|code| code == &Keycode::Escape) {
-            return PollResult::Stop;
+            return PollResult::Back;
         }
 
         let mut keymap = Keymap::default();
 
         keys.into_iter()
-            .filter_map(|key| match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xC),
-                Keycode::Q => Some(0x4),
-                Keycode::W => Some(0x5),
-                Keycode::E => Some(0x6),
-                Keycode::R => Some(0xD),
-                Keycode::A => Some(0x7),
-                Keycode::S => Some(0x8),
-                Keycode::D => Some(0x9),
-                Keycode::F => Some(0xE),
-                Keycode::Z => Some(0xA),
-                Keycode::X => Some(0x0),
-                Keycode::C => Some(0xB),
-                Keycode::V => Some(0xF),
+            .filter_map(|key| self.bindings.get(&key))
+            .for_each(|key_id| keymap.set(*key_id));
+
+        for controller in self.controllers.values() {
+            self.button_bindings
+                .iter()
+                .filter(|(button, _)| controller.button(*button))
+                .for_each(|(_, key_id)| keymap.set(*key_id));
+        }
+
+        PollResult::Keymap { keymap, hotkeys }
+    }
+
+    pub(crate) fn poll_menu(&mut self) -> Vec<MenuCommand> {
+        let mut commands = Vec::new();
+        let mut added_controllers = Vec::new();
+        for event in self.event_pump.poll_iter() {
+            let command = match event {
+                Event::Quit { .. } => Some(MenuCommand::Quit),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    added_controllers.push(which);
+                    None
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers.remove(&which);
+                    None
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => Some(MenuCommand::Redraw),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    ..
+                } => match keycode {
+                    // before Return, which selects a ROM without Alt
+                    _ if !repeat && is_fullscreen_key(keycode, keymod) => {
+                        Some(MenuCommand::ToggleFullscreen)
+                    }
+                    Keycode::Up => Some(MenuCommand::Up),
+                    Keycode::Down => Some(MenuCommand::Down),
+                    Keycode::PageUp => Some(MenuCommand::PageUp),
+                    Keycode::PageDown => Some(MenuCommand::PageDown),
+                    Keycode::Return | Keycode::KpEnter if !repeat => Some(MenuCommand::Select),
+                    // Escape may still be held from leaving a game
+                    Keycode::Escape if !repeat => Some(MenuCommand::Quit),
+                    _ => None,
+                },
+                Event::ControllerButtonDown { button, .. } => match button {
+                    Button::DPadUp => Some(MenuCommand::Up),
+                    Button::DPadDown => Some(MenuCommand::Down),
+                    Button::A | Button::Start => Some(MenuCommand::Select),
+                    _ => None,
+                },
                 _ => None,
-            })
-            .for_each(|key_id| keymap.set(key_id));
+            };
+            commands.extend(command);
+        }
+        for joystick_index in added_controllers {
+            self.open_controller(joystick_index);
+        }
 
-        PollResult::Keymap(keymap)
+        commands
+    }
+}
+
+/// The hotkey of a key press, none for keys bound to a CHIP-8 key: a
+/// binding takes precedence, so layouts can use any key.
+fn hotkey(
+    bindings: &HashMap<Keycode, usize>,
+    keycode: Keycode,
+    keymod: Mod,
+    repeat: bool,
+) -> Option<Hotkey> {
+    if bindings.contains_key(&keycode) {
+        return None;
+    }
+    match keycode {
+        _ if !repeat && is_fullscreen_key(keycode, keymod) => Some(Hotkey::ToggleFullscreen),
+        Keycode::Pause | Keycode::P if !repeat => Some(Hotkey::TogglePause),
+        // holding the key keeps stepping through frames
+        Keycode::Backslash => Some(Hotkey::AdvanceFrame),
+        Keycode::F2 if !repeat => Some(Hotkey::Reset),
+        Keycode::F3 if !repeat => Some(Hotkey::Reload),
+        Keycode::F5 => Some(Hotkey::Speed(Speed::Quarter)),
+        Keycode::F6 => Some(Hotkey::Speed(Speed::Half)),
+        Keycode::F7 => Some(Hotkey::Speed(Speed::Normal)),
+        Keycode::F8 => Some(Hotkey::Speed(Speed::Double)),
+        Keycode::F9 => Some(Hotkey::Speed(Speed::Uncapped)),
+        Keycode::Tab if !repeat => Some(Hotkey::FastForward(true)),
+        Keycode::F10 if !repeat => Some(Hotkey::ToggleRecording),
+        Keycode::M if !repeat => Some(Hotkey::ToggleMute),
+        Keycode::F12 if !repeat => Some(Hotkey::Screenshot),
+        _ => None,
+    }
+}
+
+/// F11 or Alt+Enter.
+fn is_fullscreen_key(keycode: Keycode, keymod: Mod) -> bool {
+    let alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+    keycode == Keycode::F11 || (alt && matches!(keycode, Keycode::Return | Keycode::KpEnter))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+
+    use super::*;
+    use crate::config::Layout;
+
+    fn bindings(layout: Layout) -> HashMap<Keycode, usize> {
+        let mut bindings = HashMap::new();
+        for (key_id, names) in layout.keys().iter().enumerate() {
+            for name in names.iter() {
+                bindings.insert(Keycode::from_name(name).unwrap(), key_id);
+            }
+        }
+        bindings
+    }
+
+    #[test]
+    fn p_pauses() {
+        let hotkey = hotkey(&bindings(Layout::Qwerty), Keycode::P, Mod::NOMOD, false);
+        assert!(matches!(hotkey, Some(Hotkey::TogglePause)));
+    }
+
+    #[test]
+    fn bindings_take_precedence_over_hotkeys() {
+        // Dvorak and Colemak put keypad D on P
+        for layout in [Layout::Dvorak, Layout::Colemak] {
+            let bindings = bindings(layout);
+            assert_eq!(bindings.get(&Keycode::P), Some(&0xD));
+            assert!(hotkey(&bindings, Keycode::P, Mod::NOMOD, false).is_none());
+            assert!(hotkey(&bindings, Keycode::Pause, Mod::NOMOD, false).is_some());
+        }
+        for layout in Layout::value_variants() {
+            let bindings = bindings(*layout);
+            for keycode in bindings.keys() {
+                assert!(hotkey(&bindings, *keycode, Mod::NOMOD, false).is_none());
+            }
+        }
     }
 }
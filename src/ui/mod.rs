@@ -1,30 +1,109 @@
 use anyhow::{Context, Result};
-use audio::Audio;
+use audio::{Audio, AudioCommand};
 use display::Display;
-use input::Input;
+use input::{Input, PollResult};
+
+use crate::{chip8::buzzer::Buzzer, config::Settings};
 
 pub(crate) mod audio;
+mod browser;
 pub(crate) mod display;
+mod font;
 pub(crate) mod input;
+pub(crate) mod terminal;
+
+pub use browser::Browser;
+pub use display::Scaling;
+pub use terminal::{Bell, Glyphs};
+
+/// What a game needs from the window, or from whatever else shows it.
+pub(crate) trait Frontend {
+    /// Applies the key bindings, colors and display options of a game.
+    fn setup(&mut self, settings: &Settings) -> Result<()>;
+    fn poll(&mut self) -> PollResult;
+    fn set_title(&mut self, title: &str) -> Result<()>;
+    /// Shows a screen of any resolution, `shades` go from 0 for the
+    /// background to 255 for the foreground.
+    fn render<const W: usize, const H: usize>(&mut self, shades: &[[u8; W]; H]) -> Result<()>;
+    fn toggle_fullscreen(&mut self) -> Result<()>;
+    fn audio(&mut self, command: AudioCommand);
+    /// Plays the buzzer of one emulated frame.
+    fn play_sound(&mut self, buzzer: &Buzzer);
+    /// Problems that leave the frontend working since the last call, such
+    /// as a controller that could not be opened.
+    fn take_warnings(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
 
 pub(crate) struct UI {
     pub audio: Audio,
     pub display: Display,
     pub input: Input,
+    // problems with sound, the input has its own
+    warnings: Vec<String>,
 }
 
 impl UI {
-    pub(crate) fn new() -> Result<UI> {
+    /// Opens the window and devices, sized and set up from `settings`.
+    pub(crate) fn new(settings: &Settings) -> Result<UI> {
         let sdl_context = sdl2::init().ok().context("Cannot open sdl")?;
 
-        let audio = Audio::new(&sdl_context)?;
-        let display = Display::new(&sdl_context)?;
+        let (audio, warning) = if settings.audio() {
+            Audio::new(&sdl_context)
+        } else {
+            (Audio::null(), None)
+        };
+        let display = Display::new(&sdl_context, settings.scale())?;
         let input = Input::new(&sdl_context)?;
 
         Ok(UI {
             audio,
             display,
             input,
+            warnings: warning.into_iter().collect(),
         })
     }
 }
+
+impl Frontend for UI {
+    fn setup(&mut self, settings: &Settings) -> Result<()> {
+        self.input.bind(settings)?;
+        self.display.set_palette(settings.palette());
+        self.display.set_scaling(settings.scaling());
+        self.audio.configure(&settings.beeper);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> PollResult {
+        self.input.poll()
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.display.set_title(title)
+    }
+
+    fn render<const W: usize, const H: usize>(&mut self, shades: &[[u8; W]; H]) -> Result<()> {
+        self.display.render(shades)
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<()> {
+        self.display.toggle_fullscreen()
+    }
+
+    fn audio(&mut self, command: AudioCommand) {
+        self.audio.run(command)
+    }
+
+    fn play_sound(&mut self, buzzer: &Buzzer) {
+        if let Err(err) = self.audio.play(buzzer) {
+            self.warnings.push(format!("{:#}", err));
+        }
+    }
+
+    fn take_warnings(&mut self) -> Vec<String> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.extend(self.input.take_warnings());
+        warnings
+    }
+}
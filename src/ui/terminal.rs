@@ -0,0 +1,463 @@
+//! Frontend drawing in the terminal with Unicode characters, to play over
+//! SSH on machines without a display.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::{
+    chip8::{buzzer::Buzzer, clock::Speed, keymap::Keymap},
+    config::Settings,
+    palette::{Palette, Rgb},
+};
+
+use super::{
+    audio::AudioCommand,
+    input::{Hotkey, PollResult},
+    Frontend,
+};
+
+// terminals only report key presses, so a key stays down for a few frames,
+// key repeat keeps held keys down
+const KEY_HOLD_FRAMES: u8 = 8;
+const FLASH_FRAMES: u8 = 4;
+const STDIN: i32 = 0;
+
+/// Characters the screen is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Glyphs {
+    /// Two pixels per character, in color.
+    #[default]
+    HalfBlock,
+    /// Eight pixels per character, for small terminals.
+    Braille,
+}
+
+/// How the buzzer shows in the terminal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Bell {
+    /// The terminal bell.
+    #[default]
+    Sound,
+    /// Briefly inverted colors.
+    Flash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Function(u8),
+    Escape,
+    Interrupt,
+}
+
+impl Key {
+    /// Reads the SDL key names used by the key bindings.
+    fn from_name(name: &str) -> Option<Key> {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Key::Char(lowercase(c))),
+            _ => match name {
+                "Space" => Some(Key::Char(' ')),
+                "Up" => Some(Key::Up),
+                "Down" => Some(Key::Down),
+                "Left" => Some(Key::Left),
+                "Right" => Some(Key::Right),
+                _ => None,
+            },
+        }
+    }
+}
+
+pub(crate) struct Terminal {
+    // terminal settings to restore on exit
+    original: libc::termios,
+    bindings: HashMap<Key, usize>,
+    // frames left before each CHIP-8 key is released
+    held: [u8; 16],
+    fast_forward: bool,
+    palette: Palette,
+    glyphs: Glyphs,
+    bell: Bell,
+    title: String,
+    // the last screen drawn, with its width, to redraw it when flashing
+    shades: Vec<u8>,
+    width: usize,
+    flash: u8,
+    // the buzzer at the end of the last frame
+    buzzing: bool,
+    muted: bool,
+    output: String,
+}
+
+impl Terminal {
+    /// Switches the terminal to raw mode and to the alternate screen.
+    pub(crate) fn new() -> Result<Terminal> {
+        // SAFETY: termios is plain data, filled in by tcgetattr
+        let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+        // SAFETY: the pointer is to a valid termios
+        if unsafe { libc::tcgetattr(STDIN, &mut original) } != 0 {
+            bail!("Cannot read terminal settings, is stdin a terminal?");
+        }
+
+        let mut raw = original;
+        // SAFETY: the pointer is to a valid termios
+        unsafe { libc::cfmakeraw(&mut raw) };
+        // reads return at once, with whatever input is there
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: the pointer is to a valid termios
+        if unsafe { libc::tcsetattr(STDIN, libc::TCSANOW, &raw) } != 0 {
+            bail!("Cannot switch the terminal to raw mode");
+        }
+
+        // alternate screen, hidden cursor
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+
+        Ok(Terminal {
+            original,
+            bindings: HashMap::new(),
+            held: [0; 16],
+            fast_forward: false,
+            palette: Palette::default(),
+            glyphs: Glyphs::default(),
+            bell: Bell::default(),
+            title: String::new(),
+            shades: Vec::new(),
+            width: 0,
+            flash: 0,
+            buzzing: false,
+            muted: false,
+            output: String::new(),
+        })
+    }
+
+    fn read_keys(&mut self) -> Vec<Key> {
+        let mut bytes = Vec::new();
+        let mut buffer = [0u8; 64];
+        loop {
+            // SAFETY: the buffer is valid for its whole length
+            let read = unsafe { libc::read(STDIN, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read <= 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buffer[..read as usize]);
+        }
+        parse_keys(&bytes)
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        if self.shades.is_empty() {
+            return Ok(());
+        }
+        let height = self.shades.len() / self.width;
+        let shade = |x: usize, y: usize| match y < height {
+            true => self.shades[y * self.width + x],
+            false => 0,
+        };
+        // flashing swaps the background and the foreground
+        let flash = self.flash > 0;
+        let color = |shade: u8| self.palette.shade(if flash { 255 - shade } else { shade });
+
+        let output = &mut self.output;
+        output.clear();
+        output.push_str("\x1b[H");
+        match self.glyphs {
+            Glyphs::HalfBlock => {
+                for y in (0..height).step_by(2) {
+                    let mut colors = None;
+                    for x in 0..self.width {
+                        let cell = (color(shade(x, y)), color(shade(x, y + 1)));
+                        // only write colors when they change, to keep SSH traffic down
+                        if colors != Some(cell) {
+                            let (Rgb(r, g, b), Rgb(br, bg, bb)) = cell;
+                            output.push_str(&format!(
+                                "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                                r, g, b, br, bg, bb
+                            ));
+                            colors = Some(cell);
+                        }
+                        output.push('▀');
+                    }
+                    output.push_str("\x1b[0m\r\n");
+                }
+            }
+            Glyphs::Braille => {
+                let (Rgb(r, g, b), Rgb(br, bg, bb)) = (color(255), color(0));
+                output.push_str(&format!(
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    r, g, b, br, bg, bb
+                ));
+                // dot bits of each pixel in a 2x4 cell, by row then column
+                const DOTS: [[u32; 2]; 4] =
+                    [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                for y in (0..height).step_by(4) {
+                    for x in (0..self.width).step_by(2) {
+                        let mut bits = 0;
+                        for (dy, row) in DOTS.iter().enumerate() {
+                            for (dx, bit) in row.iter().enumerate() {
+                                if x + dx < self.width && shade(x + dx, y + dy) >= 128 {
+                                    bits |= bit;
+                                }
+                            }
+                        }
+                        output.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+                    }
+                    output.push_str("\r\n");
+                }
+                output.push_str("\x1b[0m");
+            }
+        }
+        output.push_str(&self.title);
+        output.push_str("\x1b[K");
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: the pointer is to a valid termios
+        unsafe { libc::tcsetattr(STDIN, libc::TCSANOW, &self.original) };
+    }
+}
+
+impl Frontend for Terminal {
+    fn setup(&mut self, settings: &Settings) -> Result<()> {
+        let mut bindings = HashMap::new();
+        for (key_id, names) in settings.key_bindings().iter().enumerate() {
+            // names only SDL knows, such as `Keypad 8`, are left out
+            for key in names.iter().filter_map(|name| Key::from_name(name)) {
+                bindings.insert(key, key_id);
+            }
+        }
+        self.bindings = bindings;
+        self.palette = settings.palette();
+        self.glyphs = settings.glyphs.unwrap_or_default();
+        self.bell = settings.bell.unwrap_or_default();
+        print!("\x1b[2J");
+        Ok(())
+    }
+
+    fn poll(&mut self) -> PollResult {
+        let mut hotkeys = Vec::new();
+        for held in self.held.iter_mut() {
+            *held = held.saturating_sub(1);
+        }
+
+        for key in self.read_keys() {
+            if let Some(&key_id) = self.bindings.get(&key) {
+                self.held[key_id] = KEY_HOLD_FRAMES;
+                continue;
+            }
+            let hotkey = match key {
+                Key::Interrupt => return PollResult::Quit,
+                Key::Escape => return PollResult::Back,
+                Key::Char('p') => Some(Hotkey::TogglePause),
+                Key::Char('m') => Some(Hotkey::ToggleMute),
+                Key::Char('\\') => Some(Hotkey::AdvanceFrame),
+                // there is no key release to stop at, so Tab toggles
+                Key::Char('\t') => {
+                    self.fast_forward = !self.fast_forward;
+                    Some(Hotkey::FastForward(self.fast_forward))
+                }
+                Key::Function(2) => Some(Hotkey::Reset),
+                Key::Function(3) => Some(Hotkey::Reload),
+                Key::Function(5) => Some(Hotkey::Speed(Speed::Quarter)),
+                Key::Function(6) => Some(Hotkey::Speed(Speed::Half)),
+                Key::Function(7) => Some(Hotkey::Speed(Speed::Normal)),
+                Key::Function(8) => Some(Hotkey::Speed(Speed::Double)),
+                Key::Function(9) => Some(Hotkey::Speed(Speed::Uncapped)),
+                Key::Function(10) => Some(Hotkey::ToggleRecording),
+                Key::Function(12) => Some(Hotkey::Screenshot),
+                _ => None,
+            };
+            hotkeys.extend(hotkey);
+        }
+
+        if self.flash > 0 {
+            self.flash -= 1;
+            if self.flash == 0 {
+                hotkeys.push(Hotkey::Redraw);
+            }
+        }
+
+        let mut keymap = Keymap::default();
+        (0..16)
+            .filter(|key_id| self.held[*key_id] > 0)
+            .for_each(|key_id| keymap.set(key_id));
+        PollResult::Keymap { keymap, hotkeys }
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.title = title.to_string();
+        // the window title too, for terminals that show it
+        print!("\x1b]0;{}\x07", title);
+        self.draw()
+    }
+
+    fn render<const W: usize, const H: usize>(&mut self, shades: &[[u8; W]; H]) -> Result<()> {
+        self.shades.clear();
+        self.shades.extend(shades.iter().flatten());
+        self.width = W;
+        self.draw()
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn audio(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Mute => self.muted = true,
+            AudioCommand::Unmute => self.muted = false,
+            AudioCommand::Pause | AudioCommand::Speed(_) => {}
+        }
+    }
+
+    fn play_sound(&mut self, buzzer: &Buzzer) {
+        let started = buzzer.started(self.buzzing);
+        self.buzzing = buzzer.on();
+        if !started || self.muted {
+            return;
+        }
+        match self.bell {
+            Bell::Sound => {
+                print!("\x07");
+                let _ = io::stdout().flush();
+            }
+            Bell::Flash => {
+                self.flash = FLASH_FRAMES;
+                let _ = self.draw();
+            }
+        }
+    }
+}
+
+/// Decodes key presses, including the escape sequences of arrows and
+/// function keys.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        index += 1;
+        let key = match byte {
+            0x03 => Key::Interrupt,
+            0x1B => match bytes.get(index) {
+                Some(b'[' | b'O') => {
+                    // parameters, then a final byte
+                    let start = index + 1;
+                    let length = bytes[start..]
+                        .iter()
+                        .position(|byte| (0x40..=0x7E).contains(byte))
+                        .map_or(bytes.len() - start, |position| position + 1);
+                    index = start + length;
+                    match &bytes[start..index] {
+                        b"A" => Key::Up,
+                        b"B" => Key::Down,
+                        b"C" => Key::Right,
+                        b"D" => Key::Left,
+                        b"P" => Key::Function(1),
+                        b"Q" => Key::Function(2),
+                        b"R" => Key::Function(3),
+                        b"S" => Key::Function(4),
+                        sequence => match sequence.strip_suffix(b"~") {
+                            Some(number) => match number {
+                                b"11" => Key::Function(1),
+                                b"12" => Key::Function(2),
+                                b"13" => Key::Function(3),
+                                b"14" => Key::Function(4),
+                                b"15" => Key::Function(5),
+                                b"17" => Key::Function(6),
+                                b"18" => Key::Function(7),
+                                b"19" => Key::Function(8),
+                                b"20" => Key::Function(9),
+                                b"21" => Key::Function(10),
+                                b"23" => Key::Function(11),
+                                b"24" => Key::Function(12),
+                                _ => continue,
+                            },
+                            None => continue,
+                        },
+                    }
+                }
+                _ => Key::Escape,
+            },
+            b'\r' => Key::Char('\n'),
+            byte if byte.is_ascii() => Key::Char(byte.to_ascii_lowercase() as char),
+            // the first byte of a character of 2 to 4 bytes, such as é
+            _ => {
+                let start = index - 1;
+                let Some(c) = (2..=4).find_map(|length| {
+                    let c = std::str::from_utf8(bytes.get(start..start + length)?).ok()?;
+                    c.chars().next()
+                }) else {
+                    continue;
+                };
+                index = start + c.len_utf8();
+                Key::Char(lowercase(c))
+            }
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// The lowercase version of `c`, when it is a single character.
+fn lowercase(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_utf8_keys() {
+        let keys = parse_keys("aZé€É\u{1F600}\x1B[A".as_bytes());
+        assert_eq!(
+            keys,
+            [
+                Key::Char('a'),
+                Key::Char('z'),
+                Key::Char('é'),
+                Key::Char('€'),
+                Key::Char('é'),
+                Key::Char('\u{1F600}'),
+                Key::Up
+            ]
+        );
+        // invalid bytes are skipped
+        assert_eq!(parse_keys(b"\xE9a\xFF"), [Key::Char('a')]);
+    }
+
+    #[test]
+    fn matches_bindings_to_keys() {
+        assert_eq!(Key::from_name("é"), Some(Key::Char('é')));
+        assert_eq!(Key::from_name("É"), Some(Key::Char('é')));
+        assert_eq!(Key::from_name("W"), Some(Key::Char('w')));
+        assert_eq!(Key::from_name("Space"), Some(Key::Char(' ')));
+        assert_eq!(Key::from_name("Keypad 8"), None);
+    }
+}
@@ -0,0 +1,200 @@
+use chip8::{Chip8, Chip8Builder, Opcode, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+fn chip8() -> Chip8 {
+    Chip8Builder::new()
+        .rom_bytes([0x60, 0x2A, 0x12, 0x02])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn reads_memory() {
+    let chip8 = chip8();
+    assert_eq!(chip8.memory().len(), 4096);
+    assert_eq!(
+        chip8.read_memory(0x200, 4).unwrap(),
+        [0x60, 0x2A, 0x12, 0x02]
+    );
+    assert_eq!(chip8.read_memory(0xFFF, 1).unwrap(), [0]);
+    assert!(chip8.read_memory(0xFFF, 0).unwrap().is_empty());
+    assert!(chip8.read_memory(0xFFF, 2).is_err());
+    assert!(chip8.read_memory(0x1000, 1).is_err());
+    assert!(chip8.read_memory(0x200, usize::MAX).is_err());
+    assert!(chip8.read_memory(u16::MAX, usize::MAX).is_err());
+}
+
+#[test]
+fn writes_memory() {
+    let mut chip8 = chip8();
+    chip8.write_memory(0xFFE, &[1, 2]).unwrap();
+    assert_eq!(chip8.read_memory(0xFFE, 2).unwrap(), [1, 2]);
+    // nothing is written when some bytes don't fit
+    assert!(chip8.write_memory(0xFFE, &[3, 4, 5]).is_err());
+    assert!(chip8.write_memory(0x1000, &[3]).is_err());
+    assert_eq!(chip8.read_memory(0xFFE, 2).unwrap(), [1, 2]);
+
+    chip8.write_memory(0x200, &[0x61, 0x07]).unwrap();
+    assert_eq!(
+        chip8.current_instruction().unwrap(),
+        Opcode::SetConst { x: 1, nn: 7 }
+    );
+    assert_eq!(
+        chip8.instruction_at(0x202).unwrap(),
+        Opcode::Jump { addr: 0x202 }
+    );
+    assert!(chip8.instruction_at(0xFFF).is_err());
+}
+
+#[test]
+fn sets_registers() {
+    let mut chip8 = chip8();
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.registers()[0], 0x2A);
+    assert_eq!(chip8.pc(), 0x202);
+
+    chip8.registers_mut()[0xF] = 1;
+    chip8.set_pc(0x200);
+    chip8.set_i(0x300);
+    chip8.set_stack(vec![0x400]);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert_eq!((chip8.pc(), chip8.i()), (0x200, 0x300));
+    assert_eq!(chip8.stack(), [0x400]);
+}
+
+#[test]
+fn sets_timers() {
+    let mut chip8 = chip8();
+    chip8.set_delay_timer(2);
+    chip8.set_sound_timer(1);
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (2, 1));
+
+    // they count down at the end of each frame
+    chip8.step_frame().unwrap();
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (1, 0));
+    assert!(chip8.buzzer().is_on(0.5));
+    chip8.step_frame().unwrap();
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+    assert!(!chip8.buzzer().on());
+}
+
+#[test]
+fn sets_pixels() {
+    let mut chip8 = chip8();
+    let framebuffer = chip8.framebuffer();
+    assert_eq!(
+        (framebuffer.len(), framebuffer[0].len()),
+        (SCREEN_HEIGHT, SCREEN_WIDTH)
+    );
+    assert!(framebuffer.iter().flatten().all(|pixel| *pixel == 0));
+
+    chip8.set_pixel(3, 4, true);
+    // past the edges, the coordinates wrap around
+    chip8.set_pixel(SCREEN_WIDTH + 5, SCREEN_HEIGHT + 6, true);
+    assert_eq!(chip8.framebuffer()[4][3], 1);
+    assert_eq!(chip8.framebuffer()[6][5], 1);
+    chip8.set_pixel(3, 4, false);
+    assert_eq!(chip8.framebuffer()[4][3], 0);
+    assert_eq!(
+        chip8
+            .framebuffer()
+            .iter()
+            .flatten()
+            .filter(|pixel| **pixel == 1)
+            .count(),
+        1
+    );
+}
+
+/// A machine running `rom` with I set to `i`.
+fn with_i(rom: &[u8], i: u16) -> Chip8 {
+    let mut chip8 = Chip8Builder::new().rom_bytes(rom).build().unwrap();
+    chip8.set_i(i);
+    chip8
+}
+
+#[test]
+fn fails_to_access_memory_past_the_end_through_i() {
+    // FX65, FX55, FX33 and DXYN with the bytes from I past FFF
+    for rom in [[0xF0, 0x65], [0xF1, 0x55], [0xF0, 0x33], [0xD0, 0x1F]] {
+        for i in [0xFFF, 0x1000, 0x2000, u16::MAX] {
+            let mut chip8 = with_i(&rom, i);
+            assert!(
+                chip8.step_frame().is_err(),
+                "{:02X?} with I = {:#X}",
+                rom,
+                i
+            );
+        }
+    }
+    // the last byte is still there
+    let mut chip8 = with_i(&[0x60, 0x2A, 0xF0, 0x55, 0x12, 0x04], 0xFFF);
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.read_memory(0xFFF, 1).unwrap(), [0x2A]);
+    assert_eq!(chip8.i(), 0x1000);
+}
+
+#[test]
+fn wraps_i_around() {
+    // V0 = 20, I += V0
+    let mut chip8 = with_i(&[0x60, 0x20, 0xF0, 0x1E, 0x12, 0x04], 0xFFF0);
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.i(), 0x0010);
+}
+
+#[test]
+fn looks_at_the_low_nibble_for_keys_and_characters() {
+    // skip V1 = 1 when the key in V0 is down
+    let rom = [0xE0, 0x9E, 0x61, 0x01, 0x12, 0x04];
+    let mut chip8 = Chip8Builder::new().rom_bytes(rom).build().unwrap();
+    chip8.registers_mut()[0] = 0x21;
+    let mut keys = [false; 16];
+    keys[1] = true;
+    chip8.set_keys(keys);
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.registers()[1], 0);
+
+    // or when it is up
+    let rom = [0xE0, 0xA1, 0x61, 0x01, 0x12, 0x04];
+    let mut chip8 = Chip8Builder::new().rom_bytes(rom).build().unwrap();
+    chip8.registers_mut()[0] = 0xF1;
+    chip8.set_keys(keys);
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+
+    // I = character in V0
+    let rom = [0xF0, 0x29, 0x12, 0x02];
+    let mut chip8 = Chip8Builder::new()
+        .rom_bytes(rom)
+        .font_address(0x50)
+        .build()
+        .unwrap();
+    chip8.registers_mut()[0] = 0x1A;
+    chip8.step_frame().unwrap();
+    assert_eq!(chip8.i(), 0x50 + 0xA * 5);
+}
+
+#[test]
+fn fails_to_run_past_the_end_of_memory() {
+    for pc in [0xFFF, 0x1000, u16::MAX] {
+        let mut chip8 = chip8();
+        chip8.set_pc(pc);
+        assert!(chip8.step_frame().is_err(), "PC = {:#X}", pc);
+    }
+}
+
+#[test]
+fn fails_on_skips_with_a_low_nibble() {
+    // V0 = V1 = 0, then 5XY1 or 9XY1, which decode as no instruction
+    for (skip, rom) in [
+        ("5XY1", [0x60, 0x00, 0x61, 0x00, 0x50, 0x11]),
+        ("9XY1", [0x60, 0x00, 0x61, 0x00, 0x90, 0x11]),
+    ] {
+        let mut chip8 = Chip8Builder::new().rom_bytes(rom).build().unwrap();
+        assert!(chip8.instruction_at(0x204).is_err(), "{}", skip);
+        let err = chip8.step_frame().unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid opcode"), "{}", skip);
+        // the instructions before it ran
+        assert_eq!(chip8.pc(), 0x204, "{}", skip);
+    }
+}
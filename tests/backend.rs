@@ -0,0 +1,304 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chip8::{Backend, Chip8, Chip8Builder, Observer, Opcode, Platform};
+
+const FRAMES: u64 = 600;
+
+fn roms() -> Vec<PathBuf> {
+    let mut roms = ["tests", "roms/games"]
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect::<Vec<_>>();
+    roms.sort();
+    roms
+}
+
+fn build(rom: &Path, platform: Platform, backend: Backend) -> Chip8 {
+    Chip8Builder::new()
+        .rom_path(rom)
+        .unwrap()
+        .platform(platform)
+        .seed(1)
+        .backend(backend)
+        .build()
+        .unwrap()
+}
+
+/// Presses a different key for a few frames now and then, so games get past
+/// their title screens.
+fn keys(frame: u64) -> [bool; 16] {
+    let mut keys = [false; 16];
+    if frame % 40 < 10 {
+        keys[(frame / 40 * 7 % 16) as usize] = true;
+    }
+    keys
+}
+
+/// What an instruction did, as seen by an observer right after it ran.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Instruction {
+        pc: u16,
+        opcode: Opcode,
+        next: u16,
+        i: u16,
+        v: [u8; 16],
+        stack: Vec<u16>,
+        timers: (u8, u8),
+        buzzer: bool,
+    },
+    MemoryWrite(u16, u8),
+    Draw(u8, u8, u8, bool),
+}
+
+/// Logs every instruction, memory write and draw.
+struct Log(Arc<Mutex<Vec<Step>>>);
+
+impl Observer for Log {
+    fn after_instruction(&mut self, vm: &Chip8, pc: u16, opcode: Opcode) {
+        self.0.lock().unwrap().push(Step::Instruction {
+            pc,
+            opcode,
+            next: vm.pc(),
+            i: vm.i(),
+            v: *vm.registers(),
+            stack: vm.stack().to_vec(),
+            timers: (vm.delay_timer(), vm.sound_timer()),
+            buzzer: vm.buzzer().on(),
+        });
+    }
+
+    fn memory_write(&mut self, _vm: &Chip8, address: u16, value: u8) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Step::MemoryWrite(address, value));
+    }
+
+    fn draw(&mut self, _vm: &Chip8, x: u8, y: u8, height: u8, collision: bool) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Step::Draw(x, y, height, collision));
+    }
+}
+
+fn logged(mut chip8: Chip8) -> (Chip8, Arc<Mutex<Vec<Step>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    chip8.add_observer(Box::new(Log(log.clone())));
+    (chip8, log)
+}
+
+/// Takes the steps logged by `expected` and `actual` since the last call,
+/// checking that they are the same.
+fn assert_same_steps(expected: &Mutex<Vec<Step>>, actual: &Mutex<Vec<Step>>, context: &str) {
+    let expected = std::mem::take(&mut *expected.lock().unwrap());
+    let actual = std::mem::take(&mut *actual.lock().unwrap());
+    if let Some(index) = (0..expected.len().max(actual.len()))
+        .find(|index| expected.get(*index) != actual.get(*index))
+    {
+        panic!(
+            "step {} of {}: {:?} but {:?}",
+            index,
+            context,
+            expected.get(index),
+            actual.get(index)
+        );
+    }
+}
+
+fn assert_same(interpreter: &Chip8, blocks: &Chip8, context: &str) {
+    assert_eq!(interpreter.pc(), blocks.pc(), "pc of {}", context);
+    assert_eq!(interpreter.i(), blocks.i(), "I of {}", context);
+    assert_eq!(
+        interpreter.registers(),
+        blocks.registers(),
+        "V of {}",
+        context
+    );
+    assert_eq!(interpreter.stack(), blocks.stack(), "stack of {}", context);
+    assert_eq!(
+        interpreter.delay_timer(),
+        blocks.delay_timer(),
+        "delay of {}",
+        context
+    );
+    assert_eq!(
+        interpreter.sound_timer(),
+        blocks.sound_timer(),
+        "sound of {}",
+        context
+    );
+    assert_eq!(
+        interpreter.buzzer().toggles(),
+        blocks.buzzer().toggles(),
+        "buzzer of {}",
+        context
+    );
+    assert_eq!(
+        interpreter.buzzer().on(),
+        blocks.buzzer().on(),
+        "buzzer of {}",
+        context
+    );
+    assert!(
+        interpreter.memory() == blocks.memory(),
+        "memory of {}",
+        context
+    );
+    assert!(
+        interpreter.framebuffer() == blocks.framebuffer(),
+        "screen of {}",
+        context
+    );
+}
+
+/// Runs the machines `build` gives for each backend, checking after every
+/// frame that they are in the same state, and after every instruction as
+/// well when `observed`. Blocks only run one instruction at a time when
+/// observed, the other run checks the whole blocks. Both fail the same way,
+/// the error is returned.
+fn lockstep(build: impl Fn(Backend) -> Chip8, name: &str, observed: bool) -> Option<String> {
+    let (mut interpreter, interpreter_steps) = logged(build(Backend::Interpreter));
+    let (mut blocks, blocks_steps) = logged(build(Backend::Blocks));
+    if !observed {
+        interpreter.clear_observers();
+        blocks.clear_observers();
+    }
+    for frame in 0..FRAMES {
+        let context = format!("{} at frame {}", name, frame);
+        interpreter.set_keys(keys(frame));
+        blocks.set_keys(keys(frame));
+        let results = (interpreter.step_frame(), blocks.step_frame());
+        assert_same_steps(&interpreter_steps, &blocks_steps, &context);
+        assert_same(&interpreter, &blocks, &context);
+        match results {
+            (Ok(()), Ok(())) => {}
+            (Err(expected), Err(actual)) => {
+                assert_eq!(format!("{:#}", expected), format!("{:#}", actual));
+                return Some(format!("{:#}", expected));
+            }
+            (expected, actual) => panic!("{}: {:?} but {:?}", context, expected, actual),
+        }
+    }
+    None
+}
+
+fn run_in_lockstep(rom: &Path, platform: Platform, observed: bool) {
+    let name = format!("{} on {:?}", rom.display(), platform);
+    lockstep(|backend| build(rom, platform, backend), &name, observed);
+}
+
+#[test]
+fn blocks_run_in_lockstep_with_the_interpreter() {
+    for rom in roms() {
+        for platform in [Platform::OriginalChip8, Platform::Superchip] {
+            run_in_lockstep(&rom, platform, false);
+            run_in_lockstep(&rom, platform, true);
+        }
+    }
+}
+
+#[test]
+fn sees_self_modifying_code() {
+    // FX55 patches the instruction at 206 each time around the loop
+    let rom = [
+        0x70, 0x01, // 200: V0 += 1
+        0xA2, 0x07, // 202: I = 207, the low byte of the instruction at 206
+        0xF0, 0x55, // 204: save V0
+        0x61, 0x00, // 206: V1 = 0, patched to V1 = V0
+        0x12, 0x00, // 208: jump 200
+    ];
+    for backend in [Backend::Interpreter, Backend::Blocks] {
+        let mut chip8 = Chip8Builder::new()
+            .rom_bytes(rom)
+            .backend(backend)
+            .tick_rate(10)
+            .build()
+            .unwrap();
+        chip8.step_frame().unwrap();
+        assert_eq!(chip8.registers()[..2], [2, 2], "{:?}", backend);
+    }
+}
+
+#[test]
+fn sees_memory_written_between_frames() {
+    let rom = [
+        0x70, 0x01, // 200: V0 += 1
+        0x12, 0x00, // 202: jump 200
+    ];
+    for backend in [Backend::Interpreter, Backend::Blocks] {
+        let mut chip8 = Chip8Builder::new()
+            .rom_bytes(rom)
+            .backend(backend)
+            .tick_rate(10)
+            .build()
+            .unwrap();
+        chip8.step_frame().unwrap();
+        assert_eq!(chip8.registers()[0], 5, "{:?}", backend);
+        // the instruction at 200 already ran, and was decoded then
+        chip8.write_memory(0x201, &[0x02]).unwrap();
+        chip8.step_frame().unwrap();
+        assert_eq!(chip8.registers()[0], 15, "{:?}", backend);
+    }
+}
+
+#[test]
+fn fail_the_same_way_past_the_end_of_memory() {
+    // I walks up to the end of memory a byte at a time, until the
+    // instruction at 204 reads or writes past it
+    for instruction in [[0xF1, 0x65], [0xF1, 0x55], [0xF0, 0x33], [0xD0, 0x1F]] {
+        let rom = [
+            [0x60, 0x01], // 200: V0 = 1
+            [0xF0, 0x1E], // 202: I += V0
+            instruction,  // 204
+            [0x12, 0x02], // 206: jump 202
+        ]
+        .concat();
+        let build = |backend| {
+            let mut chip8 = Chip8Builder::new()
+                .rom_bytes(rom.clone())
+                .backend(backend)
+                .build()
+                .unwrap();
+            chip8.set_i(0xFF0);
+            chip8
+        };
+        for observed in [false, true] {
+            let name = format!("{:02X?}", instruction);
+            let error = lockstep(build, &name, observed);
+            assert!(
+                error.is_some_and(|error| error.starts_with("Cannot access")),
+                "{}",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn wrap_i_around_the_same_way() {
+    let rom = [
+        0x60, 0x20, // 200: V0 = 20
+        0xF0, 0x1E, // 202: I += V0
+        0xF0, 0x65, // 204: load V0
+        0x12, 0x02, // 206: jump 202
+    ];
+    let build = |backend| {
+        let mut chip8 = Chip8Builder::new()
+            .rom_bytes(rom)
+            .backend(backend)
+            .build()
+            .unwrap();
+        chip8.set_i(0xFFF0);
+        chip8
+    };
+    for observed in [false, true] {
+        assert_eq!(lockstep(build, "I += V0", observed), None);
+    }
+}
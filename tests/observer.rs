@@ -0,0 +1,76 @@
+use std::sync::{Arc, Mutex};
+
+use chip8::{Chip8, Chip8Builder, Observer, Opcode};
+
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    instructions: usize,
+    draws: usize,
+    collisions: usize,
+    memory_writes: usize,
+}
+
+struct Counter(Arc<Mutex<Counts>>);
+
+impl Observer for Counter {
+    fn after_instruction(&mut self, _vm: &Chip8, _pc: u16, _opcode: Opcode) {
+        self.0.lock().unwrap().instructions += 1;
+    }
+
+    fn memory_write(&mut self, _vm: &Chip8, _address: u16, _value: u8) {
+        self.0.lock().unwrap().memory_writes += 1;
+    }
+
+    fn draw(&mut self, _vm: &Chip8, _x: u8, _y: u8, _height: u8, collision: bool) {
+        let mut counts = self.0.lock().unwrap();
+        counts.draws += 1;
+        counts.collisions += collision as usize;
+    }
+}
+
+/// Runs `rom` for `frames` frames on another thread, which the observers
+/// being `Send` allows.
+fn count(rom: &'static str, frames: u64) -> Counts {
+    let counts = Arc::new(Mutex::new(Counts::default()));
+    let mut chip8 = Chip8Builder::new().rom_path(rom).unwrap().build().unwrap();
+    chip8.add_observer(Box::new(Counter(counts.clone())));
+    std::thread::spawn(move || {
+        for _ in 0..frames {
+            chip8.step_frame().unwrap();
+        }
+    })
+    .join()
+    .unwrap();
+    Arc::try_unwrap(counts).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn counts_draws() {
+    // two rows of six sprites, then an endless jump
+    let counts = count("tests/1-chip8-logo.ch8", 60);
+    assert_eq!(counts.draws, 12);
+    assert_eq!(counts.collisions, 0);
+    assert_eq!(counts.memory_writes, 0);
+    assert!(counts.instructions > 12);
+}
+
+#[test]
+fn counts_memory_writes() {
+    // one `save v1` and three `bcd`
+    let counts = count("tests/3-corax+.ch8", 60);
+    assert_eq!(counts.memory_writes, 2 + 3 * 3);
+}
+
+#[test]
+fn removed_observers_stop_firing() {
+    let counts = Arc::new(Mutex::new(Counts::default()));
+    let mut chip8 = Chip8Builder::new()
+        .rom_path("tests/1-chip8-logo.ch8")
+        .unwrap()
+        .build()
+        .unwrap();
+    chip8.add_observer(Box::new(Counter(counts.clone())));
+    chip8.clear_observers();
+    chip8.step_frame().unwrap();
+    assert_eq!(*counts.lock().unwrap(), Counts::default());
+}
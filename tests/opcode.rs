@@ -0,0 +1,83 @@
+use chip8::Opcode;
+
+#[test]
+fn every_word_round_trips() {
+    let mut valid = 0;
+    for raw in 0..=u16::MAX {
+        if let Ok(opcode) = Opcode::decode(raw) {
+            assert_eq!(opcode.encode(), raw, "{:04X} decodes to {:?}", raw, opcode);
+            valid += 1;
+        }
+    }
+    // 00E0 and 00EE, then 10 opcodes with 12 free bits, 11 with 8 and 11 with 4
+    assert_eq!(valid, 2 + 10 * 4096 + 11 * 256 + 11 * 16);
+}
+
+#[test]
+fn displays_cowgod_and_octo_mnemonics() {
+    let cases = [
+        (0x00E0, "CLS", "clear"),
+        (0x1208, "JP 0x208", "jump 0x208"),
+        (0x3A0F, "SE VA, 0x0F", "if va != 0x0f then"),
+        (0x6120, "LD V1, 0x20", "v1 := 0x20"),
+        (0x8127, "SUBN V1, V2", "v1 =- v2"),
+        (0xB300, "JP V0, 0x300", "jump0 0x300"),
+        (0xC1FF, "RND V1, 0xFF", "v1 := random 0xff"),
+        (0xD015, "DRW V0, V1, 5", "sprite v0 v1 5"),
+        (0xE4A1, "SKNP V4", "if v4 key then"),
+        (0xF265, "LD V2, [I]", "load v2"),
+    ];
+    for (raw, cowgod, octo) in cases {
+        let opcode = Opcode::decode(raw).unwrap();
+        assert_eq!(format!("{}", opcode), cowgod);
+        assert_eq!(format!("{:#}", opcode), octo);
+    }
+}
+
+#[test]
+fn rejects_unused_low_nibbles() {
+    assert!(Opcode::decode(0x5120).is_ok());
+    assert!(Opcode::decode(0x5121).is_err());
+    assert!(Opcode::decode(0x9121).is_err());
+    assert!(Opcode::decode(0x8128).is_err());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "does not fit in a nibble")]
+fn panics_on_registers_past_f() {
+    Opcode::SetConst { x: 16, nn: 0 }.encode();
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "is not a 12-bit address")]
+fn panics_on_addresses_past_fff() {
+    Opcode::Jump { addr: 0x1FFF }.encode();
+}
+
+#[test]
+fn encodes_jump_offsets_from_the_address() {
+    assert_eq!(Opcode::JumpOffset { addr: 0x300 }.encode(), 0xB300);
+    assert_eq!(
+        Opcode::decode(0xB1FF).unwrap(),
+        Opcode::JumpOffset { addr: 0x1FF }
+    );
+}
+
+#[test]
+#[cfg(not(debug_assertions))]
+fn keeps_the_instruction_of_fields_past_their_size() {
+    assert_eq!(Opcode::SetConst { x: 16, nn: 0 }.encode(), 0x6000);
+    assert_eq!(Opcode::Jump { addr: 0x1FFF }.encode(), 0x1FFF);
+    assert_eq!(Opcode::RegAssign { addr: 0xF123 }.encode(), 0xA123);
+    assert_eq!(
+        Opcode::Draw {
+            x: 0,
+            y: 1,
+            height: 0x15
+        }
+        .encode(),
+        0xD015
+    );
+}
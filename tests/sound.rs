@@ -0,0 +1,103 @@
+use chip8::{Chip8, Chip8Builder};
+
+/// Runs `chip8` for `frames` frames, giving how long each beep lasted, in
+/// frames.
+fn beeps(chip8: &mut Chip8, frames: u64) -> Vec<f32> {
+    let mut beeps = Vec::new();
+    let mut start = None;
+    for frame in 0..frames {
+        chip8.step_frame().unwrap();
+        let buzzer = chip8.buzzer();
+        // toggles come after the first instruction, a change at 0 is from
+        // the timers counting down between frames
+        let mut on = buzzer.is_on(0.0);
+        match start {
+            None if on => start = Some(frame as f32),
+            Some(since) if !on => {
+                beeps.push(frame as f32 - since);
+                start = None;
+            }
+            _ => {}
+        }
+        for toggle in buzzer.toggles() {
+            on = !on;
+            let time = frame as f32 + toggle;
+            match start {
+                None if on => start = Some(time),
+                Some(since) => {
+                    beeps.push(time - since);
+                    start = None;
+                }
+                None => {}
+            }
+        }
+    }
+    beeps
+}
+
+#[test]
+fn beeps_for_the_sound_timer() {
+    let mut chip8 = Chip8Builder::new()
+        .rom_path("tests/7-beep.ch8")
+        .unwrap()
+        .build()
+        .unwrap();
+    // SOS, the sound timer set to 10 or 30 frames
+    let expected = [10, 10, 10, 30, 30, 30, 10, 10, 10];
+    let beeps = beeps(&mut chip8, 300);
+    assert!(beeps.len() > expected.len(), "{:?}", beeps);
+    for (beep, frames) in beeps.iter().zip(expected) {
+        // set partway into a frame, and counting down at the end of each
+        assert!(
+            *beep > frames as f32 - 1.0 && *beep <= frames as f32,
+            "{:?}",
+            beeps
+        );
+    }
+}
+
+#[test]
+fn saves_the_buzzer_to_wav() {
+    let frames = 60;
+    let path = std::env::temp_dir().join(format!("chip8-beep-{}.wav", std::process::id()));
+    let mut chip8 = Chip8Builder::new()
+        .rom_path("tests/7-beep.ch8")
+        .unwrap()
+        .build()
+        .unwrap();
+    chip8.start_wav(&path).unwrap();
+    // samples the buzzer is on for, 735 per frame at 44.1 kHz
+    let mut on = 0;
+    for _ in 0..frames {
+        chip8.step_frame().unwrap();
+        on += (0..735)
+            .filter(|index| chip8.buzzer().is_on(*index as f32 / 735.0))
+            .count();
+    }
+    assert_eq!(chip8.stop_wav().unwrap(), Some(path.clone()));
+    let wav = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let u16_at = |offset: usize| u16::from_le_bytes([wav[offset], wav[offset + 1]]);
+    let u32_at = |offset: usize| u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap());
+    let data_size = frames * 735 * 2;
+    assert_eq!(wav.len(), 44 + data_size);
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(u32_at(4) as usize, 36 + data_size);
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(16), 16);
+    // PCM, mono, 44.1 kHz, 16 bits
+    assert_eq!((u16_at(20), u16_at(22)), (1, 1));
+    assert_eq!((u32_at(24), u32_at(28)), (44100, 88200));
+    assert_eq!((u16_at(32), u16_at(34)), (2, 16));
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(u32_at(40) as usize, data_size);
+
+    // three beeps end in the first second, each fading out over 5 ms
+    let sound = wav[44..]
+        .chunks(2)
+        .filter(|sample| *sample != [0, 0])
+        .count();
+    assert!(on > 0);
+    assert!(sound >= on && sound <= on + 3 * 221, "{} for {}", sound, on);
+}